@@ -0,0 +1,47 @@
+/* Plugin API for make-rs, used with the `load` directive.
+ *
+ * A plugin is a shared object exporting
+ *
+ *     int make_rs_plugin_setup(const struct make_rs_api *api);
+ *
+ * which registers functions, rules and hooks through `api` and returns
+ * 0 on success. Strings passed to the callbacks are copied by make-rs.
+ */
+#ifndef MAKE_RS_PLUGIN_H
+#define MAKE_RS_PLUGIN_H
+
+#include <stddef.h>
+
+#define MAKE_RS_API_VERSION 1
+
+/* Appends `len` bytes of `data` to the expansion of a function call.
+   `data` may be NULL if `len` is 0. */
+typedef void (*make_rs_write)(void *output, const char *data, size_t len);
+
+/* A function callable as $(name arg1,arg2,...) with expanded arguments. */
+typedef void (*make_rs_function)(int argc, const char **argv, void *output,
+                                 make_rs_write write);
+
+enum make_rs_event {
+    MAKE_RS_BEFORE_TARGET = 0,
+    MAKE_RS_TARGET_SUCCEEDED = 1,
+    MAKE_RS_TARGET_FAILED = 2,
+};
+
+/* Called with a `make_rs_event` around the recipe of every target. */
+typedef void (*make_rs_hook)(int event, const char *target);
+
+struct make_rs_api {
+    unsigned int version;
+    void *context;
+    void (*add_function)(void *context, const char *name,
+                         make_rs_function function);
+    /* Adds a rule; `recipe` holds one command per line. */
+    void (*add_rule)(void *context, const char *target,
+                     const char *prerequisites, const char *recipe);
+    void (*add_hook)(void *context, make_rs_hook hook);
+};
+
+int make_rs_plugin_setup(const struct make_rs_api *api);
+
+#endif
@@ -0,0 +1,86 @@
+//! The built-in implicit rules and the variables they use, so that a
+//! Makefile with just `app: main.o util.o` compiles and links `app`
+//! like it does with GNU make. `-r` leaves out the rules.
+
+use crate::expand::{Expander, Flavor, Origin};
+
+/// The file name the built-in rules are read under.
+pub const FILE: &str = "<builtin>";
+
+/// The built-in rules, read like a Makefile before the real one. Where
+/// several of them could make a target, the first one wins, and rules of
+/// the Makefile win over all of them.
+const RULES: &str = "\
+%.o: %.c
+\t$(COMPILE.c) $(OUTPUT_OPTION) $<
+%.o: %.cc
+\t$(COMPILE.cc) $(OUTPUT_OPTION) $<
+%.o: %.cpp
+\t$(COMPILE.cpp) $(OUTPUT_OPTION) $<
+%.o: %.s
+\t$(COMPILE.s) $(OUTPUT_OPTION) $<
+%: %.o
+\t$(LINK.o) $^ $(LOADLIBES) $(LDLIBS) -o $@
+%: %.c
+\t$(LINK.c) $^ $(LOADLIBES) $(LDLIBS) -o $@
+%: %.cc
+\t$(LINK.cc) $^ $(LOADLIBES) $(LDLIBS) -o $@
+%: %.cpp
+\t$(LINK.cpp) $^ $(LOADLIBES) $(LDLIBS) -o $@
+";
+
+/// The built-in variables with their values. Flags are defined as empty,
+/// like in GNU make.
+const VARIABLES: [(&str, &str); 28] = [
+    ("AR", "ar"),
+    ("ARFLAGS", "rv"),
+    ("AS", "as"),
+    ("ASFLAGS", ""),
+    ("CC", "cc"),
+    ("CFLAGS", ""),
+    ("CXX", "g++"),
+    ("CXXFLAGS", ""),
+    ("CPP", "$(CC) -E"),
+    ("CPPFLAGS", ""),
+    ("LD", "ld"),
+    ("LDFLAGS", ""),
+    ("LDLIBS", ""),
+    ("LOADLIBES", ""),
+    ("LEX", "lex"),
+    ("YACC", "yacc"),
+    ("RM", "rm -f"),
+    ("TARGET_ARCH", ""),
+    ("OUTPUT_OPTION", "-o $@"),
+    ("COMPILE.c", "$(CC) $(CFLAGS) $(CPPFLAGS) $(TARGET_ARCH) -c"),
+    (
+        "COMPILE.cc",
+        "$(CXX) $(CXXFLAGS) $(CPPFLAGS) $(TARGET_ARCH) -c",
+    ),
+    ("COMPILE.cpp", "$(COMPILE.cc)"),
+    ("COMPILE.s", "$(AS) $(ASFLAGS) $(TARGET_MACH)"),
+    ("TARGET_MACH", ""),
+    ("LINK.o", "$(CC) $(LDFLAGS) $(TARGET_ARCH)"),
+    (
+        "LINK.c",
+        "$(CC) $(CFLAGS) $(CPPFLAGS) $(LDFLAGS) $(TARGET_ARCH)",
+    ),
+    (
+        "LINK.cc",
+        "$(CXX) $(CXXFLAGS) $(CPPFLAGS) $(LDFLAGS) $(TARGET_ARCH)",
+    ),
+    ("LINK.cpp", "$(LINK.cc)"),
+];
+
+/// The built-in rules as a source for [crate::Makefile::from_sources].
+pub fn rules() -> (String, String) {
+    (FILE.to_string(), RULES.to_string())
+}
+
+/// Define the built-in variables in `expander`. Any other definition,
+/// even from the environment, takes precedence.
+pub fn define_variables(expander: &mut Expander) -> Result<(), Box<dyn std::error::Error>> {
+    for (name, value) in VARIABLES {
+        expander.assign(name, value, Flavor::Recursive, Origin::Default)?;
+    }
+    Ok(())
+}
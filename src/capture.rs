@@ -0,0 +1,121 @@
+//! Output synchronization for parallel builds (`-O`).
+//!
+//! Recipes running at the same time would interleave their output.
+//! With `-O`, the output of each recipe command goes to temporary files
+//! instead, and is printed at once when the whole recipe finished
+//! (`target`) or each command finished (`line`).
+
+use crate::engine::Engine;
+use crate::process::Exit;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// How the output of recipes is synchronized.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Recipes print straight to the terminal.
+    #[default]
+    None,
+    /// The output of every command is printed when it finished.
+    Line,
+    /// The output of the whole recipe is printed when it finished.
+    Target,
+}
+
+impl Mode {
+    /// The mode called `name` on the command line. Like in GNU make,
+    /// `recurse` is the same as `target` here.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Mode::None),
+            "line" => Some(Mode::Line),
+            "target" | "recurse" => Some(Mode::Target),
+            _ => None,
+        }
+    }
+}
+
+/// Held while a buffer is printed, so buffers aren't interleaved either.
+static PRINTING: Mutex<()> = Mutex::new(());
+
+/// Numbers the temporary files of this process.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The output of a recipe that wasn't printed yet. Whatever is left
+/// is printed when the buffer is dropped.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl Buffer {
+    /// Add the echo of a command.
+    pub fn echo(&mut self, command: &str) {
+        self.stdout.extend_from_slice(command.as_bytes());
+        self.stdout.push(b'\n');
+    }
+
+    /// Run `command` with `engine` like [Engine::run], collecting its output.
+    pub fn run(
+        &mut self,
+        engine: &Engine,
+        command: &mut Command,
+    ) -> Result<Exit, Box<dyn std::error::Error>> {
+        let stdout = TempFile::create()?;
+        let stderr = TempFile::create()?;
+        command
+            .stdout(Stdio::from(stdout.file.try_clone()?))
+            .stderr(Stdio::from(stderr.file.try_clone()?));
+        let exit = engine.run(command);
+        self.stdout.extend(std::fs::read(&stdout.path)?);
+        self.stderr.extend(std::fs::read(&stderr.path)?);
+        exit
+    }
+
+    /// Print the collected output.
+    pub fn flush(&mut self) {
+        if self.stdout.is_empty() && self.stderr.is_empty() {
+            return;
+        }
+        let _printing = PRINTING.lock().unwrap();
+        let _ = std::io::stdout().lock().write_all(&self.stdout);
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().lock().write_all(&self.stderr);
+        self.stdout.clear();
+        self.stderr.clear();
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// A temporary file that is removed when it is dropped.
+struct TempFile {
+    path: std::path::PathBuf,
+    file: std::fs::File,
+}
+
+impl TempFile {
+    fn create() -> std::io::Result<Self> {
+        let name = format!(
+            "make-rs-output-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        let file = std::fs::File::create(&path)?;
+        Ok(Self { path, file })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,42 @@
+//! Delegation of targets to `cargo build`.
+//! Cargo's own fingerprinting decides whether anything changed,
+//! so the target's recipe only runs if cargo actually rebuilt something.
+
+/// Run `cargo build` with the extra `args` and report
+/// whether any artifact was rebuilt (i.e. was not fresh).
+pub fn build(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    println!("cargo build {}", args.join(" "));
+
+    // Diagnostics are rendered to stderr by cargo itself,
+    // so stdout only carries one JSON message per line.
+    let output = std::process::Command::new("cargo")
+        .arg("build")
+        .arg("--message-format=json-render-diagnostics")
+        .args(args)
+        .stderr(std::process::Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(Box::new(crate::MakeError::BuildError));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter(|message| json_field(message, "reason") == Some("\"compiler-artifact\""))
+        .any(|message| json_field(message, "fresh") == Some("false")))
+}
+
+/// Extract the raw value of a top-level string or boolean `field`
+/// from a compact JSON object as emitted by cargo.
+/// This is not a general JSON parser, but cargo's messages
+/// are regular enough for it.
+fn json_field<'a>(message: &'a str, field: &str) -> Option<&'a str> {
+    let start = message.find(&format!("\"{}\":", field))? + field.len() + 3;
+    let value = &message[start..];
+    let end = if let Some(string) = value.strip_prefix('"') {
+        string.find('"')? + 2
+    } else {
+        value.find([',', '}'])?
+    };
+    Some(&value[..end])
+}
@@ -0,0 +1,62 @@
+//! Transparent use of a compiler cache (sccache or ccache)
+//! for compiler invocations in recipes.
+
+/// The compiler caches make-rs can use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Sccache,
+    Ccache,
+}
+
+/// Compilers whose invocations are cached.
+const COMPILERS: [&str; 8] = [
+    "cc", "c++", "gcc", "g++", "clang", "clang++", "rustc", "nvcc",
+];
+
+impl Tool {
+    pub fn program(self) -> &'static str {
+        match self {
+            Self::Sccache => "sccache",
+            Self::Ccache => "ccache",
+        }
+    }
+
+    /// Find the cache named `name` on the `PATH`, where `auto`
+    /// picks sccache or else ccache. Returns `None` if it is not installed.
+    pub fn detect(name: &str) -> Result<Option<Self>, crate::MakeError> {
+        let candidates: &[Self] = match name {
+            "auto" => &[Self::Sccache, Self::Ccache],
+            "sccache" => &[Self::Sccache],
+            "ccache" => &[Self::Ccache],
+            _ => {
+                crate::diagnostic::error(format!("unknown compiler cache '{}'", name));
+                return Err(crate::MakeError::InvalidConfig);
+            }
+        };
+        Ok(candidates
+            .iter()
+            .copied()
+            .find(|tool| on_path(tool.program())))
+    }
+
+    /// Prefix `command` with the cache if it invokes a compiler,
+    /// directly or through a cross-compiler like `aarch64-linux-gnu-gcc`.
+    pub fn wrap(self, command: &str) -> Option<String> {
+        let program = command.split_whitespace().next()?;
+        let name = program.rsplit('/').next()?;
+        let compiler = COMPILERS
+            .iter()
+            .any(|c| name == *c || name.ends_with(&format!("-{}", c)));
+        // ccache does not support rustc.
+        if !compiler || (self == Self::Ccache && name == "rustc") {
+            return None;
+        }
+        Some(format!("{} {}", self.program(), command.trim_start()))
+    }
+}
+
+/// Whether `program` is an existing file in one of the directories of `PATH`.
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
@@ -0,0 +1,42 @@
+//! `make-rs completions SHELL`: completion scripts for bash, zsh and fish.
+//! They complete goals with the targets `make-rs --list` prints for the
+//! Makefile in the current directory.
+
+use crate::MakeError;
+
+/// The targets of `make-rs --list`, one per line: the indented lines
+/// start with a target name.
+const TARGETS: &str = r"make-rs --list 2>/dev/null | sed -n 's/^  \([^ :]*\).*/\1/p'";
+
+/// The completion script for `shell`.
+pub fn script(shell: &str) -> Result<String, MakeError> {
+    let script = match shell {
+        "bash" => format!(
+            r#"_make_rs() {{
+    local targets
+    targets=$({})
+    COMPREPLY=($(compgen -W "$targets" -- "${{COMP_WORDS[COMP_CWORD]}}"))
+}}
+complete -o default -F _make_rs make-rs
+"#,
+            TARGETS
+        ),
+        "zsh" => format!(
+            r#"#compdef make-rs
+_make_rs() {{
+    local -a targets
+    targets=(${{(f)"$({})"}})
+    _describe 'target' targets
+}}
+compdef _make_rs make-rs
+"#,
+            TARGETS
+        ),
+        "fish" => format!("complete -c make-rs -f -a \"({})\"\n", TARGETS),
+        _ => {
+            crate::diagnostic::error("usage: make-rs completions bash|zsh|fish");
+            return Err(MakeError::UsageError);
+        }
+    };
+    Ok(script)
+}
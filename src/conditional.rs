@@ -0,0 +1,158 @@
+//! Conditional directives, which keep lines of a Makefile from being
+//! parsed unless a condition holds:
+//!
+//! ```make
+//! ifeq ($(CC),gcc)
+//! FLAGS = -fanalyzer
+//! else ifdef CLANG_FLAGS
+//! FLAGS = $(CLANG_FLAGS)
+//! else
+//! FLAGS =
+//! endif
+//! ```
+
+use crate::diagnostic;
+use crate::expand::{self, Expander};
+use crate::source::Line;
+use crate::MakeError;
+
+/// An `if...` directive that is still open.
+#[derive(Debug)]
+struct Branch {
+    /// Whether the lines of the current branch are parsed.
+    active: bool,
+    /// Whether one of the branches so far was active.
+    taken: bool,
+    /// Whether the directive itself is in an active branch.
+    outer: bool,
+    /// The directive, to report it if `endif` is missing.
+    line: Line,
+}
+
+/// The stack of open conditional directives.
+#[derive(Debug, Default)]
+pub struct Conditionals {
+    branches: Vec<Branch>,
+}
+
+impl Conditionals {
+    /// Whether lines are currently parsed.
+    pub fn active(&self) -> bool {
+        self.branches.last().is_none_or(|branch| branch.active)
+    }
+
+    /// Handle `line` if it is a conditional directive and return whether it was one.
+    pub fn handle(
+        &mut self,
+        line: &Line,
+        expander: &Expander,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        // Tab-indented lines are recipes, whatever they look like.
+        if line.text.starts_with(expander.recipe_prefix()) {
+            return Ok(false);
+        }
+        expander.locate(Some(line));
+        let text = line.text.trim();
+        let (keyword, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        match keyword {
+            "ifeq" | "ifneq" | "ifdef" | "ifndef" => {
+                let outer = self.active();
+                // Conditions in skipped lines are not evaluated.
+                let active = outer && condition(line, keyword, rest.trim(), expander)?;
+                self.branches.push(Branch {
+                    active,
+                    taken: active,
+                    outer,
+                    line: line.clone(),
+                });
+            }
+            "else" => {
+                let Some(branch) = self.branches.last_mut() else {
+                    diagnostic::error_in(line, "else", "'else' without 'if'");
+                    return Err(Box::new(MakeError::LineIsNotATarget));
+                };
+                let rest = rest.trim();
+                branch.active = if branch.taken || !branch.outer {
+                    false
+                } else if rest.is_empty() {
+                    true
+                } else {
+                    // `else ifeq ...` and the like.
+                    let (keyword, rest) =
+                        rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    if !["ifeq", "ifneq", "ifdef", "ifndef"].contains(&keyword) {
+                        diagnostic::error_in(line, rest, "extra text after 'else'");
+                        return Err(Box::new(MakeError::LineIsNotATarget));
+                    }
+                    condition(line, keyword, rest.trim(), expander)?
+                };
+                branch.taken |= branch.active;
+            }
+            "endif" => {
+                if self.branches.pop().is_none() {
+                    diagnostic::error_in(line, "endif", "'endif' without 'if'");
+                    return Err(Box::new(MakeError::LineIsNotATarget));
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Fail if a directive is still open at the end of the Makefile.
+    pub fn finish(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.branches.last() {
+            Some(branch) => {
+                diagnostic::error_in(&branch.line, "", "missing 'endif'");
+                Err(Box::new(MakeError::LineIsNotATarget))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Evaluate the condition of the directive `keyword` with the arguments `args`.
+fn condition(
+    line: &Line,
+    keyword: &str,
+    args: &str,
+    expander: &Expander,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    match keyword {
+        "ifdef" => Ok(expander.defined(&expander.expand_rule(args)?)),
+        "ifndef" => Ok(!expander.defined(&expander.expand_rule(args)?)),
+        _ => {
+            let Some((lhs, rhs)) = comparison(args) else {
+                diagnostic::error_in(
+                    line,
+                    args,
+                    format!("expected '{} (a,b)' or '{} \"a\" \"b\"'", keyword, keyword),
+                );
+                return Err(Box::new(MakeError::LineIsNotATarget));
+            };
+            let equal = expander.expand_rule(lhs)? == expander.expand_rule(rhs)?;
+            Ok(equal == (keyword == "ifeq"))
+        }
+    }
+}
+
+/// Split the arguments of `ifeq` and `ifneq`, either `(a,b)` or `"a" "b"`
+/// (with single or double quotes).
+fn comparison(args: &str) -> Option<(&str, &str)> {
+    if let Some(inner) = args.strip_prefix('(').and_then(|a| a.strip_suffix(')')) {
+        return match expand::split_args(inner).as_slice() {
+            [lhs, rhs] => Some((lhs.trim(), rhs.trim())),
+            _ => None,
+        };
+    }
+    let (lhs, rest) = quoted(args)?;
+    let (rhs, rest) = quoted(rest.trim_start())?;
+    rest.trim().is_empty().then_some((lhs, rhs))
+}
+
+/// Split a leading quoted string off `text`.
+fn quoted(text: &str) -> Option<(&str, &str)> {
+    let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let (inner, rest) = text[1..].split_once(quote)?;
+    Some((inner, rest))
+}
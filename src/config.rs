@@ -0,0 +1,102 @@
+//! Project configuration read from `.make-rs.toml`.
+//! Only the subset of TOML needed for make-rs settings is supported:
+//! `[section]` headers and `key = value` pairs with string,
+//! number or boolean values and single-line arrays of strings.
+
+use std::collections::HashMap;
+
+/// Name of the configuration file in the project directory.
+pub const CONFIG_FILE: &str = ".make-rs.toml";
+
+/// The settings, keyed by `section.key` (or just `key` before any section).
+#[derive(Debug, Default)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    /// Read the configuration in the current directory, if there is one.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        match std::fs::read_to_string(CONFIG_FILE) {
+            Ok(data) => Self::from_str(&data),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    /// Parse a configuration from a string.
+    pub fn from_str(data: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut values = HashMap::new();
+        let mut section = String::new();
+
+        for (index, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                crate::diagnostic::error_at(CONFIG_FILE, index + 1, "expected 'key = value'");
+                return Err(Box::new(crate::MakeError::InvalidConfig));
+            };
+            let key = if section.is_empty() {
+                key.trim().to_string()
+            } else {
+                format!("{}.{}", section, key.trim())
+            };
+            values.insert(key, parse_value(value.trim()));
+        }
+
+        Ok(Self { values })
+    }
+
+    /// Get the value of `key`, written as `section.key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// All keys directly in `section` (like `section.key`) with their values, sorted.
+    pub fn section(&self, section: &str) -> Vec<(&str, &str)> {
+        let prefix = format!("{}.", section);
+        let mut entries: Vec<(&str, &str)> = self
+            .values
+            .iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(&prefix)?, value.as_str())))
+            .filter(|(key, _)| !key.contains('.'))
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    /// Get the string array `key`, e.g. `members = ["lib", "app"]`.
+    /// A plain string is treated as a list of one value.
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        let Some(value) = self.get(key) else {
+            return Vec::new();
+        };
+        let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) else {
+            return vec![value.to_string()];
+        };
+        items
+            .split(',')
+            .map(|item| item.trim().trim_matches('"').to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+}
+
+/// Strip quotes (and a trailing comment) from a value.
+fn parse_value(value: &str) -> String {
+    if let Some(rest) = value.strip_prefix('"') {
+        let end = rest.find('"').unwrap_or(rest.len());
+        return rest[..end].replace("\\\"", "\"");
+    }
+    match value.split_once('#') {
+        Some((value, _comment)) => value.trim().to_string(),
+        None => value.to_string(),
+    }
+}
@@ -0,0 +1,93 @@
+//! Debug output for `--debug=CATEGORIES`, which tells why make-rs
+//! decided what it did. The categories are those of GNU make:
+//!
+//! - `basic`: which targets are considered and whether they are remade,
+//! - `verbose`: also how each prerequisite was checked,
+//! - `implicit`: which pattern rules are tried for each file,
+//! - `jobs`: when recipes are started and finished, and job slots.
+//!
+//! Messages go to stderr, prefixed with their category.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// A kind of debug output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Basic,
+    Verbose,
+    Implicit,
+    Jobs,
+}
+
+impl Category {
+    const ALL: [Category; 4] = [
+        Category::Basic,
+        Category::Verbose,
+        Category::Implicit,
+        Category::Jobs,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Category::Basic => "basic",
+            Category::Verbose => "verbose",
+            Category::Implicit => "implicit",
+            Category::Jobs => "jobs",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The enabled categories, one bit each.
+static ENABLED: AtomicU8 = AtomicU8::new(0);
+
+/// The categories named in the comma-separated `list`, by name or first
+/// letter. `all` enables all, `none` none, and like in GNU make,
+/// `verbose` implies `basic`. Returns the unknown name on failure.
+pub fn parse(list: &str) -> Result<Vec<Category>, String> {
+    let mut categories = Vec::new();
+    for name in list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        match name {
+            "all" | "a" => categories.extend(Category::ALL),
+            "none" | "n" => categories.clear(),
+            _ => {
+                let category = Category::ALL
+                    .into_iter()
+                    .find(|c| c.name() == name || c.name()[..1] == *name)
+                    .ok_or_else(|| name.to_string())?;
+                if category == Category::Verbose {
+                    categories.push(Category::Basic);
+                }
+                categories.push(category);
+            }
+        }
+    }
+    Ok(categories)
+}
+
+/// Print the debug output of `categories` from now on.
+pub fn enable(categories: &[Category]) {
+    for category in categories {
+        ENABLED.fetch_or(category.bit(), Ordering::Relaxed);
+    }
+}
+
+/// Whether the debug output of `category` is printed.
+pub fn enabled(category: Category) -> bool {
+    ENABLED.load(Ordering::Relaxed) & category.bit() != 0
+}
+
+/// Print `message` if `category` is enabled. The message is only
+/// formatted then.
+pub fn log(category: Category, message: impl FnOnce() -> String) {
+    if enabled(category) {
+        eprintln!("make-rs: [{}] {}", category.name(), message());
+    }
+}
@@ -0,0 +1,201 @@
+//! Reporting of errors and warnings, either as text or, with
+//! `--error-format=json`, as one JSON object per line on stderr.
+//!
+//! In JSON mode, error messages are held back until the error reaches
+//! `main`, which reports them together with the error's code.
+
+use crate::json;
+use crate::source::Line;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Whether diagnostics are printed as JSON.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Whether error messages were printed as text since the last [report].
+static EMITTED: AtomicBool = AtomicBool::new(false);
+
+/// Error messages waiting for the error they belong to.
+static PENDING: Mutex<Vec<Diagnostic>> = Mutex::new(Vec::new());
+
+/// A message, optionally pointing to a line of a file.
+#[derive(Debug)]
+struct Diagnostic {
+    message: String,
+    file: Option<String>,
+    line: Option<usize>,
+    /// The text of the line with the byte range the message is about.
+    snippet: Option<(String, Range<usize>)>,
+}
+
+impl Diagnostic {
+    /// Format as text like `make-rs: file:line: message`,
+    /// followed by the line with the relevant part underlined.
+    fn to_text(&self, level: &str) -> String {
+        let mut text = match level {
+            "error" => format!("make-rs: {}", self.located()),
+            level => format!("make-rs: {}: {}", level, self.located()),
+        };
+        if let (Some((source, span)), Some(line)) = (&self.snippet, self.line) {
+            // Tabs are shown as four spaces, so the underline lines up.
+            let width = |text: &str| text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum();
+            let gutter = " ".repeat(line.to_string().len());
+            text.push_str(&format!(
+                "\n {} | {}\n {} | {}{}",
+                line,
+                source.replace('\t', "    "),
+                gutter,
+                " ".repeat(width(&source[..span.start])),
+                "^".repeat(width(&source[span.clone()]).max(1)),
+            ));
+        }
+        text
+    }
+
+    /// The message prefixed with its location, if it has one.
+    fn located(&self) -> String {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => format!("{}:{}: {}", file, line, self.message),
+            (Some(file), None) => format!("{}: {}", file, self.message),
+            _ => self.message.clone(),
+        }
+    }
+
+    /// Format as a JSON object with the given `level`, `code` and `notes`.
+    fn to_json(&self, level: &str, code: Option<&str>, notes: &[String]) -> String {
+        // Columns are counted in characters from 1, the end is exclusive.
+        let span = match (self.line, &self.snippet) {
+            (Some(line), Some((source, span))) => format!(
+                "{{\"line\":{},\"column_start\":{},\"column_end\":{},\"text\":{}}}",
+                line,
+                source[..span.start].chars().count() + 1,
+                source[..span.end].chars().count() + 1,
+                json::string(source),
+            ),
+            (Some(line), None) => format!("{{\"line\":{}}}", line),
+            _ => "null".to_string(),
+        };
+        format!(
+            "{{\"level\":{},\"code\":{},\"message\":{},\"file\":{},\"span\":{},\"notes\":{}}}",
+            json::string(level),
+            code.map(json::string).unwrap_or_else(|| "null".to_string()),
+            json::string(&self.message),
+            self.file
+                .as_deref()
+                .map(json::string)
+                .unwrap_or_else(|| "null".to_string()),
+            span,
+            json::string_array(notes),
+        )
+    }
+}
+
+/// Switch to JSON diagnostics.
+pub fn use_json() {
+    JSON.store(true, Ordering::Relaxed);
+}
+
+/// Report an error message. The error itself is returned separately.
+pub fn error(message: impl Into<String>) {
+    emit(Diagnostic {
+        message: message.into(),
+        file: None,
+        line: None,
+        snippet: None,
+    });
+}
+
+/// Report an error message about `line` of `file`.
+pub fn error_at(file: &str, line: usize, message: impl Into<String>) {
+    emit(Diagnostic {
+        message: message.into(),
+        file: Some(file.to_string()),
+        line: Some(line),
+        snippet: None,
+    });
+}
+
+/// Report an error message about the part of the Makefile `line` that
+/// first matches `part`, which is shown underlined below the message.
+/// An empty `part` stands for the whole line.
+pub fn error_in(line: &Line, part: &str, message: impl Into<String>) {
+    let text = line.text.trim_end();
+    let span = match text.find(part).filter(|_| !part.is_empty()) {
+        Some(start) => start..start + part.len(),
+        None => {
+            let start = text.len() - text.trim_start().len();
+            start..text.len()
+        }
+    };
+    emit(Diagnostic {
+        message: message.into(),
+        file: Some(line.file.clone()),
+        line: Some(line.number),
+        snippet: Some((text.to_string(), span)),
+    });
+}
+
+/// Report a warning. Warnings are printed right away in both formats.
+pub fn warning(message: impl Into<String>) {
+    warn(Diagnostic {
+        message: message.into(),
+        file: None,
+        line: None,
+        snippet: None,
+    });
+}
+
+/// Report a warning about `line` of `file`.
+pub fn warning_at(file: &str, line: usize, message: impl Into<String>) {
+    warn(Diagnostic {
+        message: message.into(),
+        file: Some(file.to_string()),
+        line: Some(line),
+        snippet: None,
+    });
+}
+
+fn warn(diagnostic: Diagnostic) {
+    crate::eventlog::diagnostic("warning", &diagnostic.located());
+    if JSON.load(Ordering::Relaxed) {
+        eprintln!("{}", diagnostic.to_json("warning", None, &[]));
+    } else {
+        eprintln!("{}", diagnostic.to_text("warning"));
+    }
+}
+
+fn emit(diagnostic: Diagnostic) {
+    crate::eventlog::diagnostic("error", &diagnostic.located());
+    if JSON.load(Ordering::Relaxed) {
+        PENDING.lock().unwrap().push(diagnostic);
+    } else {
+        EMITTED.store(true, Ordering::Relaxed);
+        eprintln!("{}", diagnostic.to_text("error"));
+    }
+}
+
+/// Print `err` as JSON, with the first pending message as its message
+/// and the others as notes. Text diagnostics were already printed, so
+/// nothing is printed for them. Returns whether the error was reported,
+/// which it wasn't if it came without a text message.
+pub fn report(err: &(dyn std::error::Error + 'static)) -> bool {
+    if !JSON.load(Ordering::Relaxed) {
+        return EMITTED.swap(false, Ordering::Relaxed);
+    }
+    let code = match err.downcast_ref::<crate::MakeError>() {
+        Some(err) => format!("{:?}", err),
+        None => "Other".to_string(),
+    };
+    let mut pending = PENDING.lock().unwrap();
+    let mut messages = pending.drain(..);
+    let diagnostic = messages.next().unwrap_or_else(|| Diagnostic {
+        message: err.to_string(),
+        file: None,
+        line: None,
+        snippet: None,
+    });
+    let notes: Vec<String> = messages.map(|note| note.located()).collect();
+    eprintln!("{}", diagnostic.to_json("error", Some(&code), &notes));
+    true
+}
@@ -0,0 +1,186 @@
+//! Observing and cancelling a running build.
+//!
+//! Observers get an [Event] whenever the build makes progress, and a
+//! [CancellationToken] stops the build: running recipe commands are
+//! killed and no further targets are started. How commands are started
+//! and stopped is up to [crate::process].
+
+use crate::debug::{self, Category};
+use crate::process::{self, Exit, Terminal, SIGINT, SIGTERM};
+use std::ffi::c_int;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+/// How often a running command checks for cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Progress of a build.
+#[derive(Debug)]
+pub enum Event<'a> {
+    TargetStarted {
+        target: &'a str,
+    },
+    TargetFinished {
+        target: &'a str,
+        success: bool,
+    },
+    /// The target was not remade, since it is up to date.
+    TargetUpToDate {
+        target: &'a str,
+    },
+    /// A command of the target's recipe exited, with `None` for
+    /// the exit code if it was killed by a signal.
+    CommandFinished {
+        target: &'a str,
+        command: &'a str,
+        exit_code: Option<i32>,
+    },
+}
+
+/// A callback for the events of a build.
+pub type Observer = Box<dyn Fn(&Event) + Send + Sync>;
+
+/// A handle to cancel a build, which can be cloned and sent to other threads.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Request the build to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the build was asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The observers and cancellation token of a build.
+#[derive(Default)]
+pub struct Engine {
+    observers: Vec<Observer>,
+    token: CancellationToken,
+}
+
+impl std::fmt::Debug for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Engine")
+            .field("observers", &self.observers.len())
+            .field("token", &self.token)
+            .finish()
+    }
+}
+
+impl Engine {
+    /// Call `observer` for every event of the build.
+    pub fn observe(&mut self, observer: Observer) {
+        self.observers.push(observer);
+    }
+
+    /// A token that cancels this build.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Tell all observers about `event`.
+    pub fn emit(&self, event: Event) {
+        for observer in &self.observers {
+            observer(&event);
+        }
+    }
+
+    /// Fail with [crate::MakeError::Cancelled] if the build was cancelled.
+    pub fn check(&self) -> Result<(), crate::MakeError> {
+        if self.token.is_cancelled() {
+            return Err(crate::MakeError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Run `command` to completion like [Command::status], killing it if
+    /// the build is cancelled meanwhile. Its output goes straight to
+    /// the terminal while it runs.
+    pub fn run(&self, command: &mut Command) -> Result<Exit, Box<dyn std::error::Error>> {
+        self.check()?;
+        let mut child = process::spawn(command)?;
+        let pid = child.id();
+        debug::log(Category::Jobs, || format!("started process {}", pid));
+        let terminal = Terminal::give(&child);
+        loop {
+            if let Some(exit) = process::try_wait(&mut child)? {
+                debug::log(Category::Jobs, || {
+                    format!("process {} exited: {}", pid, exit.status)
+                });
+                // Ctrl-C only reached the command, which had the terminal,
+                // but it was meant for make-rs as well.
+                if terminal.is_some() && process::exit_signal(&exit.status) == Some(SIGINT) {
+                    INTERRUPTED.store(SIGINT, Ordering::Relaxed);
+                }
+                return Ok(exit);
+            }
+            if self.token.is_cancelled() {
+                // The command gets the signal make-rs was interrupted by.
+                process::terminate(&mut child, interrupt_signal().unwrap_or(SIGTERM));
+                return Err(Box::new(crate::MakeError::Cancelled));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+extern "C" {
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+}
+
+/// The signal make-rs was interrupted by, or 0. Set by the signal handler,
+/// which can do little more than that.
+static INTERRUPTED: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn on_interrupt(signal: c_int) {
+    INTERRUPTED.store(signal, Ordering::Relaxed);
+}
+
+/// Whether make-rs was interrupted (Ctrl-C) or terminated since
+/// [cancel_on_interrupt] was called.
+pub fn interrupted() -> bool {
+    interrupt_signal().is_some()
+}
+
+/// The signal (`SIGINT` or `SIGTERM`) make-rs was interrupted by, if any.
+pub fn interrupt_signal() -> Option<i32> {
+    match INTERRUPTED.load(Ordering::Relaxed) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// The token of the current build, cancelled when make-rs is interrupted.
+static CURRENT: Mutex<Option<CancellationToken>> = Mutex::new(None);
+
+/// Cancel the build with `token` when make-rs is interrupted (Ctrl-C)
+/// or terminated. With `--watch`, this is called for every build and
+/// only the latest one is cancelled.
+pub fn cancel_on_interrupt(token: CancellationToken) {
+    *CURRENT.lock().unwrap() = Some(token);
+    static WATCHER: Once = Once::new();
+    WATCHER.call_once(|| {
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+        unsafe {
+            signal(SIGINT, on_interrupt);
+            signal(SIGTERM, on_interrupt);
+        }
+        std::thread::spawn(|| loop {
+            if interrupted() {
+                crate::diagnostic::warning("interrupted, cancelling the build");
+                if let Some(token) = CURRENT.lock().unwrap().as_ref() {
+                    token.cancel();
+                }
+                return;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        });
+    });
+}
@@ -0,0 +1,82 @@
+//! `--log-json FILE`: the events of the build as JSON lines, for CI
+//! systems and dashboards.
+//!
+//! Every line is an object with the `event`, the `time` in milliseconds
+//! since the Unix epoch and fields depending on the event:
+//!
+//! ```json
+//! {"event":"target_started","time":1700000000000,"target":"app"}
+//! {"event":"command","time":1700000000000,"target":"app","command":"cc -o app main.c","exit_code":0}
+//! {"event":"target_finished","time":1700000000000,"target":"app","success":true}
+//! {"event":"up_to_date","time":1700000000000,"target":"main.o"}
+//! {"event":"error","time":1700000000000,"message":"recipe for 'app' failed (exit code 1)"}
+//! ```
+
+use crate::engine::Event;
+use crate::json;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// The log file, once it was opened.
+static LOG: Mutex<Option<std::fs::File>> = Mutex::new(None);
+
+/// Write the events of this and later builds to `path`, replacing its contents.
+pub fn open(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    *LOG.lock().unwrap() = Some(std::fs::File::create(path)?);
+    Ok(())
+}
+
+/// Log an event of the build.
+pub fn event(event: &Event) {
+    match event {
+        Event::TargetStarted { target } => write("target_started", &target_field(target)),
+        Event::TargetFinished { target, success } => write(
+            "target_finished",
+            &format!("{},\"success\":{}", target_field(target), success),
+        ),
+        Event::TargetUpToDate { target } => write("up_to_date", &target_field(target)),
+        Event::CommandFinished {
+            target,
+            command,
+            exit_code,
+        } => write(
+            "command",
+            &format!(
+                "{},\"command\":{},\"exit_code\":{}",
+                target_field(target),
+                json::string(command),
+                exit_code.map_or("null".to_string(), |code| code.to_string())
+            ),
+        ),
+    }
+}
+
+/// Log an error or warning (the `level`) with its `message`.
+pub fn diagnostic(level: &str, message: &str) {
+    write(level, &format!("\"message\":{}", json::string(message)));
+}
+
+/// The `target` field of an event.
+fn target_field(target: &str) -> String {
+    format!("\"target\":{}", json::string(target))
+}
+
+/// Write a line for `event` with the other `fields`, if the log is open.
+fn write(event: &str, fields: &str) {
+    let mut log = LOG.lock().unwrap();
+    let Some(file) = log.as_mut() else {
+        return;
+    };
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    // A log that can't be written to must not fail the build.
+    let _ = writeln!(
+        file,
+        "{{\"event\":{},\"time\":{},{}}}",
+        json::string(event),
+        time,
+        fields
+    );
+}
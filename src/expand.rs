@@ -0,0 +1,712 @@
+//! Expansion of `$(...)` and `${...}` references in recipes and rules.
+//! References to variables and functions are replaced, like `$x` for
+//! single-character names. Undefined variables expand to nothing, like
+//! in GNU make, so `$$` is the only way to pass a `$` to the shell
+//! (e.g. `$$(date)` for command substitution).
+
+use crate::functions;
+use crate::vfs::{self, Fs};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Expands references and caches the results of
+/// expensive function calls for the whole build.
+#[derive(Debug, Default)]
+pub struct Expander {
+    cache: Mutex<HashMap<String, String>>,
+    /// User-defined functions implemented by external programs.
+    external: HashMap<String, String>,
+    /// Functions registered by plugins.
+    plugins: HashMap<String, crate::plugin::Function>,
+    /// Variables defined in the Makefile, with their values.
+    variables: HashMap<String, Variable>,
+    /// Variables that are passed to recipes in their environment.
+    exported: HashSet<String>,
+    /// With `--debug=expansion`, the variables whose expansion is traced
+    /// (all expansions if empty).
+    debug: Option<Vec<String>>,
+    /// Nesting depth of the reference being expanded, for tracing.
+    depth: AtomicUsize,
+    /// Number of traced variables currently being expanded.
+    traced: AtomicUsize,
+    /// The file and line of the Makefile being parsed, for the messages
+    /// of `$(error)` and `$(warning)`.
+    location: Mutex<Option<(String, usize)>>,
+    /// The filesystem of `$(wildcard)` and the other file functions,
+    /// the real one if not set.
+    fs: Option<Arc<dyn Fs>>,
+}
+
+/// How a variable was assigned, which decides when its value is expanded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    /// `NAME = value`, expanded whenever the variable is used.
+    Recursive,
+    /// `NAME := value`, expanded once when it is defined.
+    Simple,
+}
+
+/// What an assignment does with the variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// `=`, `:=` or `::=`, which replace the value.
+    Set(Flavor),
+    /// `+=`, which appends to the value with a space in between.
+    Append,
+    /// `?=`, which only defines the variable if it is not defined yet.
+    Default,
+}
+
+/// Where a variable was defined. Definitions from a later origin
+/// take precedence over earlier ones, whatever their order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Origin {
+    /// The built-in variables.
+    Default,
+    /// The environment make-rs was started in.
+    Environment,
+    /// The Makefile (or the project file).
+    File,
+    /// A `NAME=value` argument.
+    CommandLine,
+}
+
+/// The value of a variable. Simple variables hold their expanded value.
+#[derive(Debug)]
+struct Variable {
+    value: String,
+    flavor: Flavor,
+    origin: Origin,
+}
+
+impl Expander {
+    /// Define the variable `name`. `value` is expanded whenever the variable is used.
+    pub fn define_variable(&mut self, name: &str, value: &str) {
+        if !self.overridden(name, Origin::File) {
+            self.variables.insert(
+                name.to_string(),
+                Variable {
+                    value: value.to_string(),
+                    flavor: Flavor::Recursive,
+                    origin: Origin::File,
+                },
+            );
+        }
+    }
+
+    /// Define the variable `name` with the given flavor, unless it was
+    /// defined with higher precedence. Simple variables are expanded right away.
+    pub fn assign(
+        &mut self,
+        name: &str,
+        value: &str,
+        flavor: Flavor,
+        origin: Origin,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.overridden(name, origin) {
+            return Ok(());
+        }
+        let value = match flavor {
+            Flavor::Recursive => value.to_string(),
+            Flavor::Simple => self.expand(value, &[])?,
+        };
+        self.variables.insert(
+            name.to_string(),
+            Variable {
+                value,
+                flavor,
+                origin,
+            },
+        );
+        Ok(())
+    }
+
+    /// Apply the assignment `name operator value`, unless the variable was
+    /// defined with higher precedence. Appending keeps the flavor of the
+    /// variable: the appended text of a simple variable is expanded right
+    /// away, that of a recursive one when the variable is used.
+    pub fn apply(
+        &mut self,
+        name: &str,
+        value: &str,
+        operator: Operator,
+        origin: Origin,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match operator {
+            Operator::Set(flavor) => self.assign(name, value, flavor, origin),
+            Operator::Default if self.variables.contains_key(name) => Ok(()),
+            Operator::Default => self.assign(name, value, Flavor::Recursive, origin),
+            Operator::Append => {
+                let Some(variable) = self.variables.get(name) else {
+                    return self.assign(name, value, Flavor::Recursive, origin);
+                };
+                if variable.origin > origin {
+                    return Ok(());
+                }
+                let flavor = variable.flavor;
+                let appended = match flavor {
+                    Flavor::Recursive => value.to_string(),
+                    Flavor::Simple => self.expand(value, &[])?,
+                };
+                let variable = self.variables.get_mut(name).unwrap();
+                if !variable.value.is_empty() && !appended.is_empty() {
+                    variable.value.push(' ');
+                }
+                variable.value.push_str(&appended);
+                variable.origin = origin;
+                Ok(())
+            }
+        }
+    }
+
+    /// Pass the variable `name` to recipes in their environment.
+    pub fn export(&mut self, name: &str) {
+        self.exported.insert(name.to_string());
+    }
+
+    /// Look files up in `fs` instead of the real filesystem.
+    pub fn set_fs(&mut self, fs: Arc<dyn Fs>) {
+        self.fs = Some(fs);
+    }
+
+    /// The filesystem files are looked up in.
+    fn fs(&self) -> &dyn Fs {
+        self.fs.as_deref().unwrap_or(&vfs::Os)
+    }
+
+    /// Export all variables defined so far.
+    pub fn export_all(&mut self) {
+        self.exported.extend(self.variables.keys().cloned());
+    }
+
+    /// The exported variables with their expanded values,
+    /// for the environment of a recipe.
+    pub fn environment(
+        &self,
+        automatic: &[(&str, String)],
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let mut environment = Vec::new();
+        for name in &self.exported {
+            if let Some(variable) = self.variables.get(name) {
+                let value = match variable.flavor {
+                    Flavor::Recursive => self.expand_references(&variable.value, automatic)?,
+                    Flavor::Simple => variable.value.clone(),
+                };
+                environment.push((name.clone(), value));
+            }
+        }
+        Ok(environment)
+    }
+
+    /// Whether the variable `name` is defined with a non-empty value.
+    pub fn defined(&self, name: &str) -> bool {
+        self.variables
+            .get(name)
+            .is_some_and(|variable| !variable.value.is_empty())
+    }
+
+    /// Where the variable `name` was defined, if it is defined.
+    pub fn origin(&self, name: &str) -> Option<Origin> {
+        self.variables.get(name).map(|variable| variable.origin)
+    }
+
+    /// The character recipe lines start with: the first one of
+    /// `.RECIPEPREFIX`, or a tab if it is not set.
+    pub fn recipe_prefix(&self) -> char {
+        self.variables
+            .get(".RECIPEPREFIX")
+            .and_then(|variable| variable.value.chars().next())
+            .unwrap_or('\t')
+    }
+
+    /// Report messages of `$(error)` and `$(warning)` at `line`,
+    /// or without a location if it is `None`.
+    pub fn locate(&self, line: Option<&crate::source::Line>) {
+        *self.location.lock().unwrap() = line.map(|line| (line.file.clone(), line.number));
+    }
+
+    /// Whether `name` was defined with higher precedence than `origin`.
+    fn overridden(&self, name: &str, origin: Origin) -> bool {
+        self.variables
+            .get(name)
+            .is_some_and(|variable| variable.origin > origin)
+    }
+
+    /// Register the function `name`, implemented by the executable `program`.
+    pub fn define_external(&mut self, name: &str, program: &str) {
+        self.external.insert(name.to_string(), program.to_string());
+    }
+
+    /// Register the function `name`, implemented by a plugin.
+    pub fn define_plugin(&mut self, name: &str, function: crate::plugin::Function) {
+        self.plugins.insert(name.to_string(), function);
+    }
+
+    /// Trace expansions on stderr, either all of them or, if `variables`
+    /// is not empty, those happening while expanding one of these variables.
+    pub fn debug(&mut self, variables: Vec<String>) {
+        self.debug = Some(variables);
+    }
+
+    /// Whether the current expansion is traced.
+    fn tracing(&self) -> bool {
+        match &self.debug {
+            Some(variables) => variables.is_empty() || self.traced.load(Ordering::Relaxed) > 0,
+            None => false,
+        }
+    }
+
+    /// Print a line of the trace, indented by the nesting depth.
+    fn trace(&self, line: &str) {
+        if self.tracing() {
+            let depth = self.depth.load(Ordering::Relaxed);
+            eprintln!("make-rs: {}{}", "  ".repeat(depth), line);
+        }
+    }
+
+    /// Expand all references in `text`.
+    /// `automatic` holds the automatic variables of the current target.
+    pub fn expand(
+        &self,
+        text: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        // Outermost expansions with references are traced with their result.
+        let outermost = self.depth.load(Ordering::Relaxed) == 0 && text.contains('$');
+        if outermost {
+            self.trace(&format!("expanding '{}'", text));
+        }
+        let result = self.expand_references(text, automatic);
+        if outermost {
+            if let Ok(result) = &result {
+                self.trace(&format!("=> '{}'", result));
+            }
+        }
+        result
+    }
+
+    /// Expand all references in a rule line, where there are no
+    /// automatic variables.
+    pub fn expand_rule(&self, text: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.expand(text, &[])
+    }
+
+    fn expand_references(
+        &self,
+        text: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut result = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find('$') {
+            result.push_str(&rest[..start]);
+            let (open, close) = match rest[start + 1..].chars().next() {
+                Some('(') => ('(', ')'),
+                Some('{') => ('{', '}'),
+                Some('$') => {
+                    result.push('$');
+                    rest = &rest[start + 2..];
+                    continue;
+                }
+                // Any other character is the name of a variable, like the automatic
+                // variables `$@` and `$<` or the arguments `$1`, `$2`, ... of `$(call)`.
+                Some(c) => {
+                    let name = c.to_string();
+                    rest = &rest[start + 1 + c.len_utf8()..];
+                    self.depth.fetch_add(1, Ordering::Relaxed);
+                    let value = self.reference(&name, &name, "", automatic);
+                    self.depth.fetch_sub(1, Ordering::Relaxed);
+                    result.push_str(&value?);
+                    continue;
+                }
+                // A `$` at the end stays as it is.
+                None => {
+                    result.push('$');
+                    rest = "";
+                    continue;
+                }
+            };
+            let inner_start = start + 2;
+            let Some(len) = closing(&rest[inner_start..], open, close) else {
+                // Unbalanced parentheses are passed through unchanged.
+                result.push_str(&rest[start..]);
+                return Ok(result);
+            };
+            let inner = &rest[inner_start..inner_start + len];
+            rest = &rest[inner_start + len + 1..];
+
+            // Function arguments are expanded by the function itself,
+            // so that e.g. only the chosen branch of a comparison is expanded.
+            let (name, args) = match inner.split_once(char::is_whitespace) {
+                Some((name, args)) => (name, args.trim_start()),
+                None => (inner, ""),
+            };
+            self.depth.fetch_add(1, Ordering::Relaxed);
+            let value = self.reference(inner, name, args, automatic);
+            self.depth.fetch_sub(1, Ordering::Relaxed);
+            result.push_str(&value?);
+        }
+        result.push_str(rest);
+
+        Ok(result)
+    }
+
+    /// Expand the reference `$(inner)` or `${inner}`, which is split into
+    /// `name` and `args` in case it is a function call.
+    fn reference(
+        &self,
+        inner: &str,
+        name: &str,
+        args: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(value) = self.call(name, args, automatic)? {
+            self.trace(&format!("$({}) -> '{}'", inner, value));
+            return Ok(value);
+        }
+
+        let inner = self.expand(inner, automatic)?;
+        if let Some((_, value)) = automatic.iter().find(|(var, _)| *var == inner) {
+            self.trace(&format!("$({}) -> '{}' (automatic)", inner, value));
+            Ok(value.clone())
+        } else if let Some(variable) = self.variables.get(&inner) {
+            let value = &variable.value;
+            if variable.flavor == Flavor::Simple {
+                self.trace(&format!("$({}) -> '{}' (simple)", inner, value));
+                return Ok(value.clone());
+            }
+            let chosen = self.debug.as_ref().is_some_and(|v| v.contains(&inner));
+            if chosen {
+                self.traced.fetch_add(1, Ordering::Relaxed);
+            }
+            self.trace(&format!("$({}) is defined as '{}'", inner, value));
+            let expanded = self.expand(value, automatic);
+            if let Ok(expanded) = &expanded {
+                self.trace(&format!("$({}) -> '{}'", inner, expanded));
+            }
+            if chosen {
+                self.traced.fetch_sub(1, Ordering::Relaxed);
+            }
+            expanded
+        } else {
+            self.trace(&format!("$({}) is undefined", inner));
+            Ok(String::new())
+        }
+    }
+
+    /// Call the built-in function `name`, if there is one.
+    fn call(
+        &self,
+        name: &str,
+        args: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        // Most functions take a single argument that is expanded up front.
+        let expanded = || self.expand(args, automatic);
+
+        match name {
+            "pkg-config" | "pkgconf" => {
+                let args = expanded()?;
+                self.cached(name, &args, || functions::pkg_config(&args))
+            }
+            "glob" => Ok(Some(
+                expanded()?
+                    .split_whitespace()
+                    .flat_map(|pattern| crate::glob::glob(self.fs(), pattern))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )),
+            "wildcard" => Ok(Some(
+                expanded()?
+                    .split_whitespace()
+                    .flat_map(|pattern| crate::glob::wildcard(self.fs(), pattern))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )),
+            "subst" | "patsubst" => self.substitute(name, args, automatic).map(Some),
+            "shell" => {
+                let shell = crate::shell::Shell::from_variables(self)?;
+                functions::shell(&shell, &expanded()?).map(Some)
+            }
+            "sha256" => Ok(Some(functions::sha256_hex(expanded()?.as_bytes()))),
+            "md5" => Ok(Some(functions::md5_hex(expanded()?.as_bytes()))),
+            "sha256sum" => {
+                functions::hash_files(self.fs(), &expanded()?, functions::sha256_hex).map(Some)
+            }
+            "md5sum" => {
+                functions::hash_files(self.fs(), &expanded()?, functions::md5_hex).map(Some)
+            }
+            "now" => functions::now(&expanded()?).map(Some),
+            "mtime" => functions::mtime(self.fs(), expanded()?.trim()).map(Some),
+            "intcmp" => self.intcmp(&split_args(args), automatic).map(Some),
+            "let" => self.let_(args, automatic).map(Some),
+            "foreach" => self.foreach(args, automatic).map(Some),
+            "if" => self.if_(args, automatic).map(Some),
+            "filter" | "filter-out" => {
+                let parts = split_args(args);
+                let [patterns, text @ ..] = parts.as_slice() else {
+                    unreachable!("split_args gives at least one part");
+                };
+                if text.is_empty() {
+                    crate::diagnostic::error(format!("{} needs two arguments", name));
+                    return Err(Box::new(crate::MakeError::InvalidFunctionArgument));
+                }
+                let patterns = self.expand(patterns, automatic)?;
+                let text = self.expand(&text.join(","), automatic)?;
+                Ok(Some(functions::filter(&patterns, &text, name == "filter")))
+            }
+            "error" | "warning" | "info" => self.message(name, &expanded()?).map(Some),
+            "call" => self.call_variable(args, automatic).map(Some),
+            _ if self.external.contains_key(name) || self.plugins.contains_key(name) => {
+                let args = split_args(args)
+                    .into_iter()
+                    .map(|arg| self.expand(arg, automatic))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match self.external.get(name) {
+                    Some(program) => self.cached(name, &args.join(","), || {
+                        functions::external(program, &args)
+                    }),
+                    None => crate::plugin::call(self.plugins[name], &args).map(Some),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// `$(intcmp lhs,rhs[,lt-part[,eq-part[,gt-part]]])` compares two integers
+    /// and expands the part matching the result. With only two arguments,
+    /// it gives the number if they are equal and nothing otherwise.
+    fn intcmp(
+        &self,
+        args: &[&str],
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let [lhs, rhs, parts @ ..] = args else {
+            crate::diagnostic::error("intcmp needs at least two arguments");
+            return Err(Box::new(crate::MakeError::InvalidFunctionArgument));
+        };
+        let lhs = functions::parse_int("intcmp", &self.expand(lhs, automatic)?)?;
+        let rhs = functions::parse_int("intcmp", &self.expand(rhs, automatic)?)?;
+
+        let part = match (lhs.cmp(&rhs), parts) {
+            (std::cmp::Ordering::Equal, []) => return Ok(lhs.to_string()),
+            (std::cmp::Ordering::Less, [lt, ..]) => lt,
+            (std::cmp::Ordering::Equal, [_, eq, ..]) => eq,
+            (std::cmp::Ordering::Greater, [_, _, gt, ..]) => gt,
+            // A missing gt-part defaults to the eq-part.
+            (std::cmp::Ordering::Greater, [_, eq]) => eq,
+            _ => return Ok(String::new()),
+        };
+        self.expand(part, automatic)
+    }
+
+    /// `$(subst from,to,text)` replaces every `from` in `text` by `to`,
+    /// `$(patsubst pattern,replacement,text)` does so for the words of
+    /// `text` matching `pattern`, where `%` stands for any part of a word.
+    fn substitute(
+        &self,
+        name: &str,
+        args: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let parts = split_args(args);
+        let [from, to, text @ ..] = parts.as_slice() else {
+            crate::diagnostic::error(format!("{} needs three arguments", name));
+            return Err(Box::new(crate::MakeError::InvalidFunctionArgument));
+        };
+        let from = self.expand(from, automatic)?;
+        let to = self.expand(to, automatic)?;
+        let text = self.expand(&text.join(","), automatic)?;
+        if name == "subst" {
+            return Ok(text.replace(&from, &to));
+        }
+        Ok(functions::patsubst(from.trim(), to.trim(), &text))
+    }
+
+    /// `$(let var [var ...],list,text)` binds the words of `list` to the variables
+    /// while expanding `text`. The last variable gets all remaining words.
+    fn let_(
+        &self,
+        args: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        // Only the first two commas separate arguments, the text may contain more.
+        let parts = split_args(args);
+        let [vars, list, text @ ..] = parts.as_slice() else {
+            crate::diagnostic::error("let needs three arguments");
+            return Err(Box::new(crate::MakeError::InvalidFunctionArgument));
+        };
+        let text = text.join(",");
+        let vars = self.expand(vars, automatic)?;
+        let list = self.expand(list, automatic)?;
+        let vars: Vec<&str> = vars.split_whitespace().collect();
+
+        let mut words = list.split_whitespace();
+        let mut scope: Vec<(&str, String)> = Vec::new();
+        for (i, var) in vars.iter().enumerate() {
+            let value = if i + 1 == vars.len() {
+                words.clone().collect::<Vec<_>>().join(" ")
+            } else {
+                words.next().unwrap_or_default().to_string()
+            };
+            scope.push((var, value));
+        }
+        // Bindings shadow variables of the same name from the outer scope.
+        scope.extend(automatic.iter().map(|(name, value)| (*name, value.clone())));
+
+        self.expand(&text, &scope)
+    }
+
+    /// `$(foreach var,list,text)` expands `text` once for every word of `list`,
+    /// with `var` bound to the word, and joins the results with spaces.
+    fn foreach(
+        &self,
+        args: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let parts = split_args(args);
+        let [var, list, text @ ..] = parts.as_slice() else {
+            crate::diagnostic::error("foreach needs three arguments");
+            return Err(Box::new(crate::MakeError::InvalidFunctionArgument));
+        };
+        let var = self.expand(var, automatic)?;
+        let var = var.trim();
+        let list = self.expand(list, automatic)?;
+        let text = text.join(",");
+
+        let mut results = Vec::new();
+        for word in list.split_whitespace() {
+            // The binding shadows a variable of the same name from the outer scope.
+            let scope: Vec<(&str, String)> = std::iter::once((var, word.to_string()))
+                .chain(automatic.iter().map(|(name, value)| (*name, value.clone())))
+                .collect();
+            let result = self.expand(&text, &scope)?;
+            if !result.is_empty() {
+                results.push(result);
+            }
+        }
+        Ok(results.join(" "))
+    }
+
+    /// `$(if condition,then-part[,else-part])` expands the then-part if the
+    /// condition expands to anything but whitespace, and the else-part otherwise.
+    fn if_(
+        &self,
+        args: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let parts = split_args(args);
+        let [condition, then, otherwise @ ..] = parts.as_slice() else {
+            crate::diagnostic::error("if needs at least two arguments");
+            return Err(Box::new(crate::MakeError::InvalidFunctionArgument));
+        };
+        if !self.expand(condition, automatic)?.trim().is_empty() {
+            self.expand(then, automatic)
+        } else {
+            self.expand(&otherwise.join(","), automatic)
+        }
+    }
+
+    /// `$(call var,arg1,arg2,...)` expands the variable `var` with `$(1)`,
+    /// `$(2)`, ... bound to the arguments and `$(0)` to the name of the variable.
+    fn call_variable(
+        &self,
+        args: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let parts = split_args(args);
+        let name = self.expand(parts[0], automatic)?;
+        let name = name.trim();
+        let mut scope = vec![("0".to_string(), name.to_string())];
+        for (i, arg) in parts[1..].iter().enumerate() {
+            scope.push(((i + 1).to_string(), self.expand(arg, automatic)?));
+        }
+        let Some(variable) = self.variables.get(name) else {
+            return Ok(String::new());
+        };
+        // Arguments of an outer call are not visible.
+        let outer = automatic
+            .iter()
+            .filter(|(var, _)| !var.chars().all(|c| c.is_ascii_digit()))
+            .map(|(var, value)| (*var, value.clone()));
+        let scope: Vec<(&str, String)> = scope
+            .iter()
+            .map(|(var, value)| (var.as_str(), value.clone()))
+            .chain(outer)
+            .collect();
+        self.expand(&variable.value, &scope)
+    }
+
+    /// `$(error text)` fails with `text`, `$(warning text)` reports it
+    /// as a warning and `$(info text)` prints it. The latter two expand to nothing.
+    fn message(&self, name: &str, text: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let location = self.location.lock().unwrap().clone();
+        match (name, location) {
+            ("info", _) => println!("{}", text),
+            ("warning", Some((file, line))) => crate::diagnostic::warning_at(&file, line, text),
+            ("warning", None) => crate::diagnostic::warning(text),
+            (_, Some((file, line))) => {
+                crate::diagnostic::error_at(&file, line, text);
+                return Err(Box::new(crate::MakeError::ErrorFunction));
+            }
+            (_, None) => {
+                crate::diagnostic::error(text);
+                return Err(Box::new(crate::MakeError::ErrorFunction));
+            }
+        }
+        Ok(String::new())
+    }
+
+    /// Look up the result of `name args` in the cache,
+    /// computing and storing it with `compute` if it is missing.
+    fn cached(
+        &self,
+        name: &str,
+        args: &str,
+        compute: impl FnOnce() -> Result<String, Box<dyn std::error::Error>>,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let key = format!("{} {}", name, args);
+        if let Some(value) = self.cache.lock().unwrap().get(&key) {
+            return Ok(Some(value.clone()));
+        }
+        let value = compute()?;
+        self.cache.lock().unwrap().insert(key, value.clone());
+        Ok(Some(value))
+    }
+}
+
+/// Split function arguments at commas which are not inside a nested reference.
+pub fn split_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&args[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&args[start..]);
+    parts
+}
+
+/// Find the index of the `close` bracket closing an already opened `open` one.
+fn closing(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if c == open => depth += 1,
+            _ if c == close && depth == 0 => return Some(i),
+            _ if c == close => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
@@ -0,0 +1,69 @@
+//! Reasons for remaking a target, as reported by `--explain`.
+
+use crate::vfs::Fs;
+use std::time::Duration;
+
+/// Why a target is remade.
+#[derive(Debug)]
+pub enum Reason {
+    /// The target file does not exist.
+    Missing,
+    /// A prerequisite is newer than the target by the given duration.
+    NewerPrerequisite(String, Duration),
+    /// A prerequisite without a file of its own (like a phony target) was remade.
+    RemadePrerequisite(String),
+    /// Cargo rebuilt some of the target's artifacts.
+    CargoRebuilt,
+    /// `-B` treats all targets as out of date.
+    AlwaysMake,
+    /// The target is `.PHONY`.
+    Phony,
+    /// With `--hash`, the contents of a prerequisite changed since the last build.
+    ChangedPrerequisite(String),
+    /// With `--hash`, the recipe changed since the last build.
+    ChangedRecipe,
+}
+
+impl std::fmt::Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Reason::Missing => write!(f, "it does not exist"),
+            Reason::NewerPrerequisite(name, by) => {
+                write!(
+                    f,
+                    "prerequisite '{}' is newer by {:.1}s",
+                    name,
+                    by.as_secs_f64()
+                )
+            }
+            Reason::RemadePrerequisite(name) => write!(f, "prerequisite '{}' was remade", name),
+            Reason::CargoRebuilt => write!(f, "cargo rebuilt its artifacts"),
+            Reason::AlwaysMake => write!(f, "--always-make was given"),
+            Reason::Phony => write!(f, "it is phony"),
+            Reason::ChangedPrerequisite(name) => write!(f, "prerequisite '{}' changed", name),
+            Reason::ChangedRecipe => write!(f, "its recipe changed"),
+        }
+    }
+}
+
+/// Work out whether and why `target` with the given prerequisite files
+/// in `fs` needs to be remade, by comparing their modification times.
+/// `None` means that the target is up to date.
+pub fn reason(fs: &dyn Fs, target: &str, prerequisites: &[String]) -> Option<Reason> {
+    let Some(target_modified) = fs.modified(target).ok() else {
+        return Some(Reason::Missing);
+    };
+    prerequisites
+        .iter()
+        .filter_map(|prerequisite| {
+            let newer_by = fs
+                .modified(prerequisite)
+                .ok()?
+                .duration_since(target_modified)
+                .ok()?;
+            Some((prerequisite, newer_by))
+        })
+        .filter(|(_, newer_by)| !newer_by.is_zero())
+        .max_by_key(|(_, newer_by)| *newer_by)
+        .map(|(prerequisite, newer_by)| Reason::NewerPrerequisite(prerequisite.clone(), newer_by))
+}
@@ -0,0 +1,27 @@
+//! The targets that failed in the last build, for `--retry-failed`.
+
+use crate::vfs::Fs;
+
+/// Where the failed targets are stored, one per line.
+const FAILED_FILE: &str = ".make-rs/failed";
+
+/// The targets that failed in the last build.
+pub fn read(fs: &dyn Fs) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    match fs.read_to_string(FAILED_FILE) {
+        Ok(data) => Ok(data.lines().map(str::to_string).collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(Box::new(err)),
+    }
+}
+
+/// Replace the stored targets with the `failed` ones of this build.
+pub fn record(fs: &dyn Fs, failed: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if failed.is_empty() {
+        return match fs.remove(FAILED_FILE) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(Box::new(err)),
+            _ => Ok(()),
+        };
+    }
+    fs.write(FAILED_FILE, (failed.join("\n") + "\n").as_bytes())?;
+    Ok(())
+}
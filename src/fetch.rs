@@ -0,0 +1,162 @@
+//! Downloading of URL and git prerequisites into a local cache.
+//! The actual transfers are done by `curl` and `git`, which keeps
+//! make-rs free of HTTP client and git dependencies. Like recipes, they
+//! work on the real filesystem, while make-rs looks at the cache through
+//! the [Fs] of the build.
+
+use crate::vfs::Fs;
+use std::path::{Path, PathBuf};
+
+/// Directory that downloaded prerequisites are cached in.
+const CACHE_DIR: &str = ".make-rs/cache";
+
+/// Directory that git prerequisites are checked out in.
+const GIT_DIR: &str = ".make-rs/git";
+
+/// Is this dependency a URL that has to be downloaded?
+pub fn is_url(dep: &str) -> bool {
+    dep.starts_with("https://") || dep.starts_with("http://")
+}
+
+/// Split a dependency of the form `git+<url>@<rev>` into the
+/// repository URL and revision. Returns `None` for other dependencies.
+pub fn split_git(dep: &str) -> Option<(&str, &str)> {
+    dep.strip_prefix("git+")?.rsplit_once('@')
+}
+
+/// The path inside `dir` that `url` is stored at.
+/// The file name is kept recognizable, while a hash of the
+/// full URL keeps different URLs from colliding.
+fn cache_path_in(dir: &str, url: &str) -> PathBuf {
+    let name = url
+        .rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .unwrap_or("download");
+    let digest = crate::hash::to_hex(&crate::hash::sha256(url.as_bytes()));
+    Path::new(dir).join(format!("{}-{}", &digest[..16], name))
+}
+
+/// The path inside the cache that `url` is downloaded to.
+pub fn cache_path(url: &str) -> PathBuf {
+    cache_path_in(CACHE_DIR, url)
+}
+
+/// The path that the git repository at `url` is checked out at.
+pub fn checkout_path(url: &str) -> PathBuf {
+    cache_path_in(GIT_DIR, url.trim_end_matches(".git"))
+}
+
+/// Make sure an up-to-date copy of `url` is in the cache and return its path.
+/// If a copy already exists, the request is made conditional on its
+/// ETag and modification time, so unchanged files are not downloaded again.
+pub fn fetch(fs: &dyn Fs, url: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = cache_path(url).to_string_lossy().into_owned();
+    // curl needs the directory on the real filesystem.
+    std::fs::create_dir_all(CACHE_DIR)?;
+
+    let partial = format!("{}.part", path);
+    let etag = format!("{}.etag", path);
+
+    let mut curl = std::process::Command::new("curl");
+    curl.arg("--fail")
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--location")
+        .arg("--etag-save")
+        .arg(&etag)
+        .arg("--output")
+        .arg(&partial);
+    if fs.exists(&path) {
+        curl.arg("--time-cond").arg(&path);
+        if fs.exists(&etag) {
+            curl.arg("--etag-compare").arg(&etag);
+        }
+    }
+    curl.arg(url);
+
+    println!("fetch {}", url);
+    let status = curl.status()?;
+    if !status.success() {
+        let _ = fs.remove(&partial);
+        crate::diagnostic::error(format!("failed to download {}", url));
+        return Err(Box::new(crate::MakeError::DownloadFailed));
+    }
+
+    // curl only creates the output file if the server sent a body,
+    // i.e. the cached copy was missing or stale.
+    if fs.exists(&partial) {
+        fs.rename(&partial, &path)?;
+    }
+
+    Ok(path.into())
+}
+
+/// Make sure the git repository at `url` is checked out at `rev`
+/// and return the path of the checkout. Each repository is cloned
+/// once and only fetched again if `rev` is not known locally or
+/// names a branch, which may have moved.
+pub fn checkout(fs: &dyn Fs, url: &str, rev: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = checkout_path(url);
+    let dir = path.to_string_lossy().into_owned();
+
+    if !fs.exists(&dir) {
+        std::fs::create_dir_all(GIT_DIR)?;
+        println!("clone {}", url);
+        git(&["clone", "--quiet", "--no-checkout", url, &dir])?;
+    } else if !git_succeeds(&dir, &["rev-parse", "--verify", "--quiet", &commit(rev)])
+        || git_succeeds(
+            &dir,
+            &["rev-parse", "--verify", "--quiet", &remote_branch(rev)],
+        )
+    {
+        println!("fetch {}", url);
+        git(&["-C", &dir, "fetch", "--quiet", "--tags", "origin"])?;
+    }
+
+    // Prefer the remote branch, so a stale local branch left
+    // over from the initial clone is not checked out.
+    let target = if git_succeeds(
+        &dir,
+        &["rev-parse", "--verify", "--quiet", &remote_branch(rev)],
+    ) {
+        remote_branch(rev)
+    } else {
+        commit(rev)
+    };
+    git(&["-C", &dir, "checkout", "--quiet", "--detach", &target])?;
+
+    Ok(path)
+}
+
+/// The revision `rev` peeled to a commit.
+fn commit(rev: &str) -> String {
+    format!("{}^{{commit}}", rev)
+}
+
+/// The remote tracking branch for `rev`, if it is a branch name.
+fn remote_branch(rev: &str) -> String {
+    format!("refs/remotes/origin/{}", rev)
+}
+
+/// Run git with `args` and fail the build if it fails.
+fn git(args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let status = std::process::Command::new("git").args(args).status()?;
+    if !status.success() {
+        crate::diagnostic::error(format!("git {} failed", args.join(" ")));
+        return Err(Box::new(crate::MakeError::DownloadFailed));
+    }
+    Ok(())
+}
+
+/// Run a git query in the repository at `dir`, discarding its output.
+fn git_succeeds(dir: &str, args: &[&str]) -> bool {
+    std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
@@ -0,0 +1,114 @@
+//! Content hashes for `--hash`, which decides whether a target is out of
+//! date by what its prerequisites contain and what its expanded recipe
+//! runs instead of by modification times.
+//!
+//! The hashes each target was last built with are kept in
+//! `.make-rs/state` as `target\tsha256\tprerequisite` lines, and a
+//! `target\tsha256` line for the recipe.
+
+use crate::explain::Reason;
+use crate::hash;
+use crate::vfs::Fs;
+use std::collections::HashMap;
+
+/// Where the hashes are stored.
+const STATE_FILE: &str = ".make-rs/state";
+
+/// The hashes of a target's recipe and prerequisites.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Fingerprint {
+    recipe: String,
+    /// Each prerequisite with the hash of its contents.
+    prerequisites: Vec<(String, String)>,
+}
+
+impl Fingerprint {
+    /// Hash the expanded recipe `commands` and the contents of the `prerequisites`
+    /// in `fs`. Prerequisites that can't be read, like directories,
+    /// get an empty hash.
+    pub fn new(fs: &dyn Fs, commands: &[String], prerequisites: &[String]) -> Self {
+        let recipe = hash::to_hex(&hash::sha256(commands.join("\n").as_bytes()));
+        let prerequisites = prerequisites
+            .iter()
+            .map(|prerequisite| {
+                let digest = fs
+                    .read(prerequisite)
+                    .map(|data| hash::to_hex(&hash::sha256(&data)))
+                    .unwrap_or_default();
+                (prerequisite.clone(), digest)
+            })
+            .collect();
+        Self {
+            recipe,
+            prerequisites,
+        }
+    }
+
+    /// Why a target that was built with `previous` is out of date now,
+    /// or `None` if nothing changed.
+    pub fn changes(&self, previous: &Fingerprint) -> Option<Reason> {
+        if self.recipe != previous.recipe {
+            return Some(Reason::ChangedRecipe);
+        }
+        // A prerequisite that changed or was added, or else one that was removed.
+        self.prerequisites
+            .iter()
+            .find(|entry| !previous.prerequisites.contains(entry))
+            .or_else(|| {
+                previous
+                    .prerequisites
+                    .iter()
+                    .find(|entry| !self.prerequisites.contains(entry))
+            })
+            .map(|(prerequisite, _)| Reason::ChangedPrerequisite(prerequisite.clone()))
+    }
+}
+
+/// Read the fingerprints of the targets. A missing file has none.
+pub fn read(fs: &dyn Fs) -> Result<HashMap<String, Fingerprint>, Box<dyn std::error::Error>> {
+    let data = match fs.read_to_string(STATE_FILE) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(Box::new(err)),
+    };
+    let mut fingerprints: HashMap<String, Fingerprint> = HashMap::new();
+    for line in data.lines() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(target), Some(digest)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let fingerprint = fingerprints.entry(target.to_string()).or_default();
+        match fields.next() {
+            Some(prerequisite) => fingerprint
+                .prerequisites
+                .push((prerequisite.to_string(), digest.to_string())),
+            None => fingerprint.recipe = digest.to_string(),
+        }
+    }
+    Ok(fingerprints)
+}
+
+/// Store the `fingerprints` of the targets that were built or found up
+/// to date in this build, keeping those of the other targets.
+pub fn record(
+    fs: &dyn Fs,
+    fingerprints: &[(String, Fingerprint)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if fingerprints.is_empty() {
+        return Ok(());
+    }
+    let mut state = read(fs)?;
+    state.extend(fingerprints.iter().cloned());
+
+    let mut entries: Vec<_> = state.into_iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut data = String::new();
+    for (target, fingerprint) in entries {
+        data.push_str(&format!("{}\t{}\n", target, fingerprint.recipe));
+        for (prerequisite, digest) in &fingerprint.prerequisites {
+            data.push_str(&format!("{}\t{}\t{}\n", target, digest, prerequisite));
+        }
+    }
+    fs.write(STATE_FILE, data.as_bytes())?;
+    Ok(())
+}
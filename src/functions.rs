@@ -0,0 +1,247 @@
+//! Built-in functions available in `$(...)` expansions.
+
+use crate::vfs::Fs;
+use crate::MakeError;
+
+/// Query pkg-config, e.g. `$(pkg-config --cflags glib-2.0)`.
+/// The package names are checked first, so a missing package
+/// gives a clear error instead of an empty expansion.
+pub fn pkg_config(args: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let args: Vec<&str> = args.split_whitespace().collect();
+    for package in args.iter().filter(|arg| !arg.starts_with('-')) {
+        let exists = std::process::Command::new("pkg-config")
+            .arg("--exists")
+            .arg(package)
+            .status()?;
+        if !exists.success() {
+            crate::diagnostic::error(format!("pkg-config: package '{}' not found", package));
+            return Err(Box::new(MakeError::PackageNotFound));
+        }
+    }
+
+    let output = std::process::Command::new("pkg-config")
+        .args(&args)
+        .output()?;
+    if !output.status.success() {
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        return Err(Box::new(MakeError::PackageNotFound));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Replace the words of `text` matching `pattern` by `replacement`, with the
+/// part matched by the first `%` of `pattern` substituted for the first `%`
+/// of `replacement`, e.g. `$(patsubst %.c,%.o,main.c util.c)`.
+pub fn patsubst(pattern: &str, replacement: &str, text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| match pattern.split_once('%') {
+            Some((prefix, suffix)) => match word
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(suffix))
+            {
+                Some(stem) => replacement.replacen('%', stem, 1),
+                None => word.to_string(),
+            },
+            None if word == pattern => replacement.to_string(),
+            None => word.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The words of `text` that match one of `patterns` (or, if `keep` is false,
+/// that match none of them), e.g. `$(filter %.c %.h,$(FILES))`.
+pub fn filter(patterns: &str, text: &str, keep: bool) -> String {
+    let matches = |word: &str| {
+        patterns
+            .split_whitespace()
+            .any(|pattern| match pattern.split_once('%') {
+                Some((prefix, suffix)) => {
+                    word.len() >= prefix.len() + suffix.len()
+                        && word.starts_with(prefix)
+                        && word.ends_with(suffix)
+                }
+                None => word == pattern,
+            })
+    };
+    text.split_whitespace()
+        .filter(|word| matches(word) == keep)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Run `command` with `shell`, e.g. `$(shell git rev-parse HEAD)`.
+/// Its output becomes the expansion with newlines turned into spaces.
+/// Like in GNU make, a failing command is not an error.
+pub fn shell(
+    shell: &crate::shell::Shell,
+    command: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let output = shell
+        .command(command)
+        .stderr(std::process::Stdio::inherit())
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.trim_end_matches('\n').replace('\n', " "))
+}
+
+/// Hash each of the files in `args` with `hash`, e.g. `$(sha256sum foo.tar.gz)`.
+pub fn hash_files(
+    fs: &dyn Fs,
+    args: &str,
+    hash: fn(&[u8]) -> String,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut digests = Vec::new();
+    for file in args.split_whitespace() {
+        match fs.read(file) {
+            Ok(data) => digests.push(hash(&data)),
+            Err(err) => {
+                crate::diagnostic::error(format!("cannot hash {}: {}", file, err));
+                return Err(Box::new(MakeError::DependencyDoesNotExist));
+            }
+        }
+    }
+    Ok(digests.join(" "))
+}
+
+/// Hex-encoded SHA-256 digest of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    crate::hash::to_hex(&crate::hash::sha256(data))
+}
+
+/// Hex-encoded MD5 digest of `data`.
+pub fn md5_hex(data: &[u8]) -> String {
+    crate::hash::to_hex(&crate::hash::md5(data))
+}
+
+/// The current time, formatted with strftime-like `format`, e.g. `$(now %Y-%m-%d)`.
+/// Without a format, the time is given in ISO 8601. If `SOURCE_DATE_EPOCH` is set,
+/// it is used instead of the clock, so builds stay reproducible.
+pub fn now(format: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let seconds = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.trim().parse()?,
+        Err(_) => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64,
+    };
+    let format = if format.is_empty() {
+        "%Y-%m-%dT%H:%M:%SZ"
+    } else {
+        format
+    };
+    Ok(format_time(seconds, format))
+}
+
+/// The modification time of `file` in seconds since the Unix epoch, e.g. `$(mtime foo.o)`.
+pub fn mtime(fs: &dyn Fs, file: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let modified = match fs.modified(file) {
+        Ok(modified) => modified,
+        Err(err) => {
+            crate::diagnostic::error(format!("cannot get mtime of {}: {}", file, err));
+            return Err(Box::new(MakeError::DependencyDoesNotExist));
+        }
+    };
+    Ok(modified
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs()
+        .to_string())
+}
+
+/// Format `time` in ISO 8601 (in UTC).
+pub fn iso8601(time: std::time::SystemTime) -> String {
+    let seconds = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default();
+    format_time(seconds, "%Y-%m-%dT%H:%M:%SZ")
+}
+
+/// Format a Unix timestamp (in UTC) using the strftime
+/// conversions `%Y %m %d %H %M %S %j %s %F %T %%`.
+fn format_time(seconds: i64, format: &str) -> String {
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    let day_of_year = days - days_from_civil(year, 1, 1) + 1;
+
+    let mut result = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => result.push_str(&year.to_string()),
+            Some('m') => result.push_str(&format!("{:02}", month)),
+            Some('d') => result.push_str(&format!("{:02}", day)),
+            Some('H') => result.push_str(&format!("{:02}", time / 3600)),
+            Some('M') => result.push_str(&format!("{:02}", time / 60 % 60)),
+            Some('S') => result.push_str(&format!("{:02}", time % 60)),
+            Some('j') => result.push_str(&format!("{:03}", day_of_year)),
+            Some('s') => result.push_str(&seconds.to_string()),
+            Some('F') => result.push_str(&format_time(seconds, "%Y-%m-%d")),
+            Some('T') => result.push_str(&format_time(seconds, "%H:%M:%S")),
+            Some('%') => result.push('%'),
+            Some(other) => {
+                result.push('%');
+                result.push(other);
+            }
+            None => result.push('%'),
+        }
+    }
+    result
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) date.
+/// See <http://howardhinnant.github.io/date_algorithms.html>.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert a (year, month, day) date to days since the Unix epoch.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Parse the integer argument of `function`.
+pub fn parse_int(function: &str, arg: &str) -> Result<i64, Box<dyn std::error::Error>> {
+    arg.trim().parse().map_err(|_| {
+        crate::diagnostic::error(format!(
+            "non-numeric argument to {}: '{}'",
+            function,
+            arg.trim()
+        ));
+        Box::new(MakeError::InvalidFunctionArgument) as Box<dyn std::error::Error>
+    })
+}
+
+/// Run the external `program` implementing a user-defined function with `args`.
+/// Its output becomes the expansion, with newlines turned into spaces like `$(shell)`.
+pub fn external(program: &str, args: &[String]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .stderr(std::process::Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        crate::diagnostic::error(format!("function {} failed", program));
+        return Err(Box::new(MakeError::BuildError));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.trim_end_matches('\n').replace('\n', " "))
+}
@@ -0,0 +1,168 @@
+//! Glob patterns: the basic ones of `$(wildcard)` with `*`, `?` and
+//! `[...]`, and extended ones with `**` recursion and `{a,b}` alternation.
+
+use crate::vfs::Fs;
+
+/// Find all paths in `fs` matching the extended glob `pattern`, sorted.
+/// Braces are expanded first, so every alternative is a plain glob.
+pub fn glob(fs: &dyn Fs, pattern: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    for pattern in expand_braces(pattern) {
+        find(fs, &pattern, true, &mut paths);
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Find all paths in `fs` matching the basic glob `pattern`, sorted, like
+/// the `wildcard` function of GNU make: `**` is just `*` and braces are
+/// taken literally.
+pub fn wildcard(fs: &dyn Fs, pattern: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    find(fs, pattern, false, &mut paths);
+    paths.sort();
+    paths
+}
+
+/// Add the paths matching `pattern` to `paths`, where `**` descends
+/// into subdirectories if `recursive`.
+fn find(fs: &dyn Fs, pattern: &str, recursive: bool, paths: &mut Vec<String>) {
+    let (root, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => ("/".to_string(), rest),
+        None => (String::new(), pattern),
+    };
+    let components: Vec<&str> = rest.split('/').filter(|c| !c.is_empty()).collect();
+    walk(fs, &root, &components, recursive, paths);
+}
+
+/// Expand `{a,b}` alternations (which may be nested) into separate patterns.
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    // Find the first top-level group and the commas separating its alternatives.
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let mut depth = 0;
+    let mut separators = Vec::new();
+    let mut close = None;
+    for (i, c) in pattern[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => separators.push(open + i),
+            _ => {}
+        }
+    }
+    let Some(close) = close else {
+        return vec![pattern.to_string()];
+    };
+
+    let prefix = &pattern[..open];
+    let suffix = &pattern[close + 1..];
+    let mut bounds = vec![open];
+    bounds.extend(separators);
+    bounds.push(close);
+
+    bounds
+        .windows(2)
+        .flat_map(|w| expand_braces(&format!("{}{}{}", prefix, &pattern[w[0] + 1..w[1]], suffix)))
+        .collect()
+}
+
+/// Match the remaining path `components` against the directory `dir`.
+fn walk(fs: &dyn Fs, dir: &str, components: &[&str], recursive: bool, paths: &mut Vec<String>) {
+    let Some((component, rest)) = components.split_first() else {
+        if !dir.is_empty() {
+            paths.push(dir.trim_end_matches('/').to_string());
+        }
+        return;
+    };
+
+    if *component == "**" && recursive {
+        // `**` matches zero directories...
+        walk(fs, dir, rest, recursive, paths);
+        // ...or descends into every subdirectory.
+        for entry in entries(fs, dir) {
+            let path = format!("{}{}", dir, entry);
+            if fs.is_dir(&path) {
+                walk(fs, &format!("{}/", path), components, recursive, paths);
+            }
+        }
+    } else if !component.contains(['*', '?', '[']) {
+        let path = format!("{}{}", dir, component);
+        if fs.exists(&path) {
+            descend(fs, &path, rest, recursive, paths);
+        }
+    } else {
+        for entry in entries(fs, dir) {
+            if matches(component, &entry) {
+                descend(fs, &format!("{}{}", dir, entry), rest, recursive, paths);
+            }
+        }
+    }
+}
+
+/// Continue matching below `path`, which matched the previous component.
+fn descend(fs: &dyn Fs, path: &str, rest: &[&str], recursive: bool, paths: &mut Vec<String>) {
+    if rest.is_empty() {
+        paths.push(path.to_string());
+    } else if fs.is_dir(path) {
+        walk(fs, &format!("{}/", path), rest, recursive, paths);
+    }
+}
+
+/// The non-hidden entries of `dir`, where an empty `dir` is the current directory.
+fn entries(fs: &dyn Fs, dir: &str) -> Vec<String> {
+    let dir = if dir.is_empty() { "." } else { dir };
+    let mut entries = fs.read_dir(dir).unwrap_or_default();
+    entries.retain(|name| !name.starts_with('.'));
+    entries
+}
+
+/// Match a single path component against a pattern
+/// containing `*`, `?` and `[...]` character classes.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches_chars(&pattern, &text)
+}
+
+fn matches_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|i| matches_chars(&pattern[1..], &text[i..])),
+        Some('?') => !text.is_empty() && matches_chars(&pattern[1..], &text[1..]),
+        Some('[') => {
+            let Some(end) = pattern.iter().skip(1).position(|&c| c == ']') else {
+                return text.first() == Some(&'[') && matches_chars(&pattern[1..], &text[1..]);
+            };
+            let class = &pattern[1..end + 1];
+            let Some(&c) = text.first() else {
+                return false;
+            };
+            let (negated, class) = match class.first() {
+                Some('!' | '^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    found |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    found |= class[i] == c;
+                    i += 1;
+                }
+            }
+            found != negated && matches_chars(&pattern[end + 2..], &text[1..])
+        }
+        Some(&p) => text.first() == Some(&p) && matches_chars(&pattern[1..], &text[1..]),
+    }
+}
@@ -0,0 +1,69 @@
+//! `make-rs graph`: the dependency graph as Graphviz DOT, e.g. for
+//! `make-rs graph | dot -Tsvg > graph.svg`.
+//!
+//! Targets are boxes, phony targets and targets without a file (like `all`
+//! or `clean`) are dashed ellipses and prerequisites without a rule are grey notes.
+//! Edges to order-only prerequisites are dashed.
+
+use crate::{Dependency, MakeError, Makefile};
+
+/// The graph of `goals` and everything they depend on.
+pub fn dot(makefile: &Makefile, goals: &[String]) -> Result<String, MakeError> {
+    let mut targets = Vec::new();
+    for goal in goals {
+        for target in makefile.resolve(goal)? {
+            if !targets
+                .iter()
+                .any(|t: &&crate::Target| t.name == target.name)
+            {
+                targets.push(target);
+            }
+        }
+    }
+
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    let mut files: Vec<&str> = Vec::new();
+    for target in &targets {
+        let style = if !target.phony && makefile.fs.exists(&target.file()) {
+            "shape=box"
+        } else {
+            "shape=ellipse, style=dashed"
+        };
+        nodes.push(format!("  {} [{}];", quote(&target.name), style));
+
+        let order_only = target.order_only.iter().map(|dep| (dep, true));
+        for (dep, order_only) in target
+            .dependencies
+            .iter()
+            .map(|d| (d, false))
+            .chain(order_only)
+        {
+            let (dep, name, _) = makefile.dependency(dep);
+            if !matches!(dep, Dependency::Target(_)) && !files.contains(&name) {
+                files.push(name);
+            }
+            let style = if order_only { " [style=dashed]" } else { "" };
+            edges.push(format!(
+                "  {} -> {}{};",
+                quote(&target.name),
+                quote(name),
+                style
+            ));
+        }
+    }
+    for file in files {
+        nodes.push(format!("  {} [shape=note, color=grey];", quote(file)));
+    }
+
+    Ok(format!(
+        "digraph make {{\n  rankdir=LR;\n{}\n{}\n}}\n",
+        nodes.join("\n"),
+        edges.join("\n")
+    ))
+}
+
+/// Quote `id` for DOT.
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
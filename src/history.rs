@@ -0,0 +1,91 @@
+//! A persistent history of how long each target's recipe took and how
+//! much CPU time it used, to estimate how long building it again will
+//! take and how many cores it keeps busy.
+
+use crate::vfs::Fs;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Where the history is stored, as `target\tmilliseconds` lines, with
+/// the milliseconds of CPU time in a third column where they are known.
+const HISTORY_FILE: &str = ".make-rs/durations";
+
+/// What is known about a target's recipe from previous builds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Entry {
+    /// How long the recipe took.
+    pub duration: Duration,
+    /// The CPU time it used, if it is known.
+    pub cpu: Option<Duration>,
+}
+
+impl Entry {
+    /// How many cores the recipe kept busy on average, at least one.
+    pub fn cores(&self) -> usize {
+        match self.cpu {
+            Some(cpu) if !self.duration.is_zero() => (cpu.as_secs_f64()
+                / self.duration.as_secs_f64())
+            .round()
+            .max(1.0) as usize,
+            _ => 1,
+        }
+    }
+}
+
+/// Read the history. A missing history is empty.
+pub fn read(fs: &dyn Fs) -> Result<HashMap<String, Entry>, Box<dyn std::error::Error>> {
+    let data = match fs.read_to_string(HISTORY_FILE) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(Box::new(err)),
+    };
+    let millis = |field: &str| field.parse().ok().map(Duration::from_millis);
+    Ok(data
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let target = fields.next()?;
+            let entry = Entry {
+                duration: millis(fields.next()?)?,
+                cpu: fields.next().and_then(millis),
+            };
+            Some((target.to_string(), entry))
+        })
+        .collect())
+}
+
+/// Add the entries of this build to the history. Each target's entry is
+/// averaged with its previous one, so that a single slow or fast build
+/// does not replace what is known about the target.
+pub fn record(fs: &dyn Fs, entries: &[(String, Entry)]) -> Result<(), Box<dyn std::error::Error>> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut history = read(fs)?;
+    for (target, entry) in entries {
+        let average = match history.get(target) {
+            Some(previous) => Entry {
+                duration: (previous.duration + entry.duration) / 2,
+                cpu: match (previous.cpu, entry.cpu) {
+                    (Some(previous), Some(cpu)) => Some((previous + cpu) / 2),
+                    (previous, cpu) => cpu.or(previous),
+                },
+            },
+            None => *entry,
+        };
+        history.insert(target.clone(), average);
+    }
+
+    let mut entries: Vec<_> = history.into_iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut data = String::new();
+    for (target, entry) in entries {
+        data.push_str(&format!("{}\t{}", target, entry.duration.as_millis()));
+        if let Some(cpu) = entry.cpu {
+            data.push_str(&format!("\t{}", cpu.as_millis()));
+        }
+        data.push('\n');
+    }
+    fs.write(HISTORY_FILE, data.as_bytes())?;
+    Ok(())
+}
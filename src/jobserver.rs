@@ -0,0 +1,171 @@
+//! The GNU make jobserver, which shares the job slots of `-j N` with
+//! recursive invocations and other tools that speak its protocol (like
+//! cargo or gcc's LTO), so nested builds don't run N jobs each.
+//!
+//! The jobserver is a pipe holding one byte (a token) per job slot but
+//! one. Every process may run one job without a token; each further job
+//! reads a token first and writes it back when it finished. make-rs
+//! creates a named pipe (`fifo:PATH`), and also uses the pipe file
+//! descriptors (`R,W`) of an outer GNU make. The pipe is passed on in
+//! `MAKEFLAGS` as `--jobserver-auth=...`.
+//!
+//! Named pipes and inherited descriptors only exist on Unix. On Windows,
+//! there is no jobserver, so `-j N` only limits the jobs of one make-rs.
+
+use crate::debug::{self, Category};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(unix)]
+extern "C" {
+    fn mkfifo(path: *const std::ffi::c_char, mode: std::ffi::c_uint) -> std::ffi::c_int;
+    fn fcntl(fd: std::ffi::c_int, cmd: std::ffi::c_int, ...) -> std::ffi::c_int;
+}
+
+/// `F_GETFD`, which is 1 on all Unix platforms make-rs runs on.
+#[cfg(unix)]
+const F_GETFD: std::ffi::c_int = 1;
+
+/// A connection to a jobserver.
+#[derive(Debug)]
+pub struct Client {
+    read: File,
+    write: File,
+    /// How the jobserver is given in `MAKEFLAGS`.
+    auth: String,
+    /// The named pipe, if this client created it.
+    created: Option<std::path::PathBuf>,
+    /// Whether the job slot that needs no token is free.
+    implicit: AtomicBool,
+}
+
+/// A job slot, which is given back when it is dropped.
+pub struct Token<'a> {
+    client: &'a Client,
+    /// The byte read from the pipe, or `None` for the slot that needs no token.
+    byte: Option<u8>,
+}
+
+impl Drop for Token<'_> {
+    fn drop(&mut self) {
+        match self.byte {
+            Some(byte) => {
+                debug::log(Category::Jobs, || {
+                    "returning a token to the jobserver".to_string()
+                });
+                let _ = (&self.client.write).write_all(&[byte]);
+            }
+            None => self.client.implicit.store(true, Ordering::Release),
+        }
+    }
+}
+
+impl Client {
+    /// Create a jobserver for `jobs` job slots. Fails with
+    /// [std::io::ErrorKind::Unsupported] where there are no named pipes.
+    #[cfg(unix)]
+    pub fn create(jobs: usize) -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("make-rs-jobserver-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let name = std::ffi::CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        // SAFETY: `name` is a valid C string for the duration of the call.
+        if unsafe { mkfifo(name.as_ptr(), 0o600) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // Opening the pipe for reading and writing doesn't wait for a writer.
+        let pipe = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        (&pipe).write_all(&vec![b'+'; jobs.saturating_sub(1)])?;
+        Ok(Self {
+            read: pipe.try_clone()?,
+            write: pipe,
+            auth: format!("fifo:{}", path.display()),
+            created: Some(path),
+            implicit: AtomicBool::new(true),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn create(_jobs: usize) -> std::io::Result<Self> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    /// Connect to the jobserver of an outer make given by `auth`, which
+    /// is `fifo:PATH` or `R,W`. Returns `None` if it is not available,
+    /// e.g. because the outer make didn't pass the pipe on.
+    #[cfg(unix)]
+    pub fn open(auth: &str) -> Option<Self> {
+        let (read, write) = match auth.strip_prefix("fifo:") {
+            Some(path) => {
+                let pipe = std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(path)
+                    .ok()?;
+                (pipe.try_clone().ok()?, pipe)
+            }
+            None => {
+                use std::ffi::c_int;
+                use std::os::fd::FromRawFd;
+
+                let (read, write) = auth.split_once(',')?;
+                let (read, write): (c_int, c_int) = (read.parse().ok()?, write.parse().ok()?);
+                // SAFETY: `fcntl` only looks the descriptors up.
+                let open = |fd| fd >= 0 && unsafe { fcntl(fd, F_GETFD) } != -1;
+                if !open(read) || !open(write) {
+                    return None;
+                }
+                // SAFETY: the descriptors are open and were passed to make-rs to use.
+                unsafe { (File::from_raw_fd(read), File::from_raw_fd(write)) }
+            }
+        };
+        Some(Self {
+            read,
+            write,
+            auth: auth.to_string(),
+            created: None,
+            implicit: AtomicBool::new(true),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn open(_auth: &str) -> Option<Self> {
+        None
+    }
+
+    /// How the jobserver is given in `MAKEFLAGS`, after `--jobserver-auth=`.
+    pub fn auth(&self) -> &str {
+        &self.auth
+    }
+
+    /// Take a job slot, waiting until one is free.
+    pub fn acquire(&self) -> std::io::Result<Token<'_>> {
+        if self.implicit.swap(false, Ordering::Acquire) {
+            return Ok(Token {
+                client: self,
+                byte: None,
+            });
+        }
+        debug::log(Category::Jobs, || {
+            "waiting for a token from the jobserver".to_string()
+        });
+        let mut byte = [0];
+        (&self.read).read_exact(&mut byte)?;
+        Ok(Token {
+            client: self,
+            byte: Some(byte[0]),
+        })
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Some(path) = &self.created {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
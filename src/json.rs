@@ -0,0 +1,25 @@
+//! Helpers for writing JSON without a serialization dependency.
+
+/// Quote and escape `value` as a JSON string.
+pub fn string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Format `values` as a JSON array of strings.
+pub fn string_array<T: AsRef<str>>(values: &[T]) -> String {
+    let values: Vec<String> = values.iter().map(|value| string(value.as_ref())).collect();
+    format!("[{}]", values.join(","))
+}
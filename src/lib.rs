@@ -0,0 +1,2618 @@
+//! A subset of the `make` utility.
+//!
+//! Besides the `make-rs` command line, the parser and executor can be
+//! embedded in other tools:
+//!
+//! ```no_run
+//! let mut makefile = make_rs::Makefile::parse("all:\n\techo hello\n")?;
+//! for target in makefile.resolve("all")? {
+//!     println!("{} <- {:?}", target.name(), target.dependencies());
+//! }
+//! makefile.build("all")?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [Makefile::observe] reports the progress of builds as [Event]s, and a
+//! [CancellationToken] stops a build from another thread:
+//!
+//! ```
+//! use make_rs::{Event, Makefile};
+//!
+//! let mut makefile = Makefile::parse("all:\n\t@true\n")?;
+//! makefile.observe(|event| {
+//!     if let Event::TargetFinished { target, success } = event {
+//!         println!("{}: {}", target, if *success { "done" } else { "failed" });
+//!     }
+//! });
+//! // `token.cancel()`, e.g. from a UI thread, stops the build.
+//! let token = makefile.cancellation_token();
+//! makefile.build("all")?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [Makefile::with_fs] checks targets in a [vfs::Fs] other than the
+//! real filesystem, like an in-memory one for tests.
+
+mod builtin;
+mod capture;
+mod cargo;
+mod ccache;
+mod completions;
+mod conditional;
+mod config;
+mod debug;
+mod diagnostic;
+mod engine;
+mod eventlog;
+mod expand;
+mod explain;
+mod failures;
+mod fetch;
+mod fingerprint;
+mod functions;
+mod glob;
+mod graph;
+mod hash;
+mod history;
+mod jobserver;
+mod json;
+mod list;
+mod manifest;
+mod notify;
+mod outputs;
+mod pattern;
+mod plan;
+mod plugin;
+mod process;
+mod profile;
+mod provenance;
+mod recursive;
+mod sbom;
+mod schedule;
+mod script;
+mod service;
+mod shell;
+mod sign;
+mod source;
+mod space;
+mod state;
+pub mod vfs;
+mod vpath;
+mod watch;
+mod workspace;
+
+pub use engine::{CancellationToken, Event, Observer};
+
+/// A [Makefile] is represented as a list of [Target]s.
+#[derive(Debug)]
+pub struct Makefile {
+    targets: Vec<Target>,
+    expander: expand::Expander,
+    /// The filesystem targets and prerequisites are looked up in.
+    fs: std::sync::Arc<dyn vfs::Fs>,
+    /// Execution hooks registered by plugins.
+    hooks: Vec<plugin::Hook>,
+    /// Observers and cancellation of the build.
+    engine: engine::Engine,
+    /// Targets that failed during this build, with why.
+    failed: std::sync::Mutex<Vec<(String, String)>>,
+    /// Targets whose recipe ran successfully during this build.
+    built: std::sync::Mutex<Vec<String>>,
+    /// Intermediate files created during this build, to be deleted afterwards.
+    intermediates: std::sync::Mutex<Vec<String>>,
+    /// Targets made during this build, with whether a recipe ran for them
+    /// (`None` if they failed).
+    made: std::sync::Mutex<std::collections::HashMap<String, Option<bool>>>,
+    /// Commands that ran during this build, for deduplication.
+    ran: std::sync::Mutex<std::collections::HashSet<String>>,
+    /// The number of bytes that must be free before a recipe is started.
+    min_free_space: Option<u64>,
+    /// How many recipes may run at the same time.
+    jobs: usize,
+    /// With `-j`, the jobserver whose job slots recipes run in.
+    jobserver: Option<jobserver::Client>,
+    /// When the recipes that ran during this build started and how long they took.
+    timings: std::sync::Mutex<Vec<profile::Timing>>,
+    /// With `--hash`, the fingerprints the targets were last built with.
+    fingerprints: std::collections::HashMap<String, fingerprint::Fingerprint>,
+    /// With `--hash`, the fingerprints of the targets that are up to date now.
+    hashed: std::sync::Mutex<Vec<(String, fingerprint::Fingerprint)>>,
+    /// Services started during this build.
+    services: std::sync::Mutex<Vec<(String, std::process::Child)>>,
+    /// The compiler cache compiler invocations are run through.
+    compiler_cache: Option<ccache::Tool>,
+    /// The variables and functions of each workspace member.
+    members: std::collections::HashMap<String, expand::Expander>,
+    /// The directories missing prerequisites are searched in.
+    search_path: vpath::SearchPath,
+    /// With `.ONESHELL`, all lines of a recipe run in one shell.
+    oneshell: bool,
+    /// With `.NOTPARALLEL` alone, the build runs serially even with `-j`.
+    not_parallel: bool,
+    options: Options,
+}
+
+/// What make-rs was asked to do.
+#[derive(Debug, Default, Clone, PartialEq)]
+enum Command {
+    /// Build the goals.
+    #[default]
+    Build,
+    /// Print a software bill of materials for the goals.
+    Sbom,
+    /// Print the dependency graph of the goals as Graphviz DOT.
+    Graph,
+    /// Check the tree against the manifest of built outputs.
+    Verify,
+    /// List the known build products, optionally of the goals.
+    Outputs,
+    /// Print what building the goals would do, without doing it.
+    Plan,
+    /// Stop the service goals and start them again.
+    Restart,
+    /// Export or import the build state.
+    State,
+    /// Print a completion script for a shell.
+    Completions,
+    /// Print the usage.
+    Help,
+    /// Print the version.
+    Version,
+}
+
+/// The usage printed by `--help`.
+const USAGE: &str = "\
+Usage: make-rs [COMMAND] [OPTIONS] [NAME=VALUE...] [TARGET...] [-- ARGS...]
+
+Commands:
+  sbom                      print a software bill of materials for the targets
+  graph                     print the dependency graph as Graphviz DOT
+  verify                    check the tree against the manifest of built outputs
+  outputs                   list the known build products
+  plan                      print what building the targets would do
+  restart                   stop the service targets and start them again
+  state export|import FILE  export or import the build state
+  completions bash|zsh|fish print a completion script for the shell
+
+Options:
+  -C, --directory DIR       change to DIR before doing anything
+  -f, --file FILE           read FILE as the Makefile
+  -j, --jobs [N]            run N recipes at once (as many as there are CPUs without N)
+  -O, --output-sync[=MODE]  with -j, print the output of each recipe (target)
+                            or command (line) at once
+  -k, --keep-going          build as much as possible after a target failed
+  -B, --always-make         remake all targets, even if they are up to date
+  -r, --no-builtin-rules    don't use the built-in implicit rules
+      --watch               build again whenever a source file changes
+      --hash                remake targets when the contents of prerequisites
+                            or the recipe changed, not by modification times
+  -q, --question            run nothing, exit with 1 if a target is out of date
+  -W, --what-if FILE        treat FILE as just modified
+  -o, --assume-old FILE     treat FILE as very old and don't remake it
+  -t, --touch               touch the targets instead of running their recipes
+  -s, --silent              don't echo recipe commands
+      --shell PROGRAM       run recipes with PROGRAM instead of SHELL
+      --explain             print why each target is remade
+      --trace               like --explain, and echo silent commands too
+      --profile[=FILE]      print how long each recipe took, and write
+                            a Chrome trace of the build to FILE
+      --list                list the targets instead of building
+      --category NAME       only list the targets of this category
+      --format FORMAT       list as text, long (with prerequisites) or json
+      --exclude PATTERN     treat the targets matching PATTERN as up to date
+      --retry-failed        build the targets that failed in the last build
+      --strict              fail when a deprecated target is made
+      --flatten             inline recursive make invocations into the build
+      --compiler-cache[=TOOL]
+                            run compilers through sccache or ccache
+      --min-free-space SIZE pause before recipes while less space is free
+      --provenance          write a provenance attestation for every artifact
+      --sign                sign the built artifacts
+      --manifest            record the built outputs in the manifest
+      --error-format=json   print diagnostics as JSON
+      --log-json FILE       write the events of the build to FILE as JSON lines
+      --debug[=CATEGORIES]  print why make-rs decides what it does, for the
+                            comma-separated categories basic (the default),
+                            verbose, implicit, jobs, all or none
+      --debug=expansion[=VARS]
+                            trace the expansion of (some) variables
+  -h, --help                print this help
+  -v, --version             print the version
+
+The exit status is 0 on success, 1 if -q found a target out of date
+and 2 on errors.
+";
+
+/// Options given on the command line.
+#[derive(Debug, Default, Clone)]
+struct Options {
+    command: Command,
+    goals: Vec<String>,
+    /// Write a provenance attestation for every built artifact.
+    provenance: bool,
+    /// Sign the built artifacts after a successful build.
+    sign: bool,
+    /// Record the built outputs in the manifest.
+    manifest: bool,
+    /// Print why each target is remade.
+    explain: bool,
+    /// With `--trace`, also echo the commands that are silent otherwise.
+    trace: bool,
+    /// Fail instead of warning when a deprecated target is made.
+    strict: bool,
+    /// List the targets instead of building.
+    list: bool,
+    /// Only list the targets of this category.
+    category: Option<String>,
+    /// How to list the targets (`text`, `long` or `json`).
+    list_format: Option<String>,
+    /// Print diagnostics as JSON.
+    json_errors: bool,
+    /// Where to write the events of the build as JSON lines.
+    log_json: Option<String>,
+    /// Inline recursive make invocations into the build graph.
+    flatten: bool,
+    /// The compiler cache to use (`auto`, `sccache` or `ccache`), if any.
+    compiler_cache: Option<String>,
+    /// Arguments after `--`, passed to recipes as `$(ARGS)`.
+    args: Vec<String>,
+    /// The categories of debug output given with `--debug`.
+    debug: Vec<debug::Category>,
+    /// With `--debug=expansion`, the variables whose expansion is traced
+    /// (all if empty).
+    debug_expansion: Option<Vec<String>>,
+    /// Pause before recipes while less space than this is free.
+    min_free_space: Option<String>,
+    /// The Makefiles given with `-f`, read in order.
+    files: Vec<String>,
+    /// The shell given with `--shell`, which overrides `SHELL`.
+    shell: Option<String>,
+    /// The directories given with `-C`, each relative to the one before.
+    directories: Vec<String>,
+    /// Don't echo recipe commands.
+    silent: bool,
+    /// Build as much as possible after a target failed.
+    keep_going: bool,
+    /// Treat all targets as out of date.
+    always_make: bool,
+    /// Leave out the built-in implicit rules.
+    no_builtin_rules: bool,
+    /// Compare the contents of prerequisites and recipes instead of modification times.
+    hash: bool,
+    /// With `--profile`, where to write a Chrome trace of the build
+    /// (empty for only printing how long the recipes took).
+    profile: Option<String>,
+    /// Build again whenever a file the build reads changes.
+    watch: bool,
+    /// Only tell whether the goals are up to date with the exit status.
+    question: bool,
+    /// Files given with `-W`, which are treated as just modified.
+    what_if: Vec<String>,
+    /// Files given with `-o`, which are treated as very old and not remade.
+    assume_old: Vec<String>,
+    /// Touch the targets that are out of date instead of running their recipes.
+    touch: bool,
+    /// `NAME=value` arguments, which override the variables of the Makefile.
+    variables: Vec<String>,
+    /// With `-j`, how many recipes may run at the same time
+    /// (empty for as many as there are CPUs).
+    jobs: Option<String>,
+    /// With `-j`, how the output of recipes running at the same time is kept apart.
+    output_sync: capture::Mode,
+    /// The jobserver given with `--jobserver-auth` in `MAKEFLAGS`, which
+    /// recursive invocations share.
+    jobserver: Option<String>,
+    /// Build the targets that failed in the last build.
+    retry_failed: bool,
+    /// Glob patterns of targets that are treated as up to date.
+    exclude: Vec<String>,
+}
+
+impl Options {
+    /// An expander with the built-in variables and the variables of the
+    /// environment and those given on the command line, for parsing a
+    /// Makefile. The latter two are exported.
+    fn expander(&self) -> Result<expand::Expander, Box<dyn std::error::Error>> {
+        let mut expander = expand::Expander::default();
+        builtin::define_variables(&mut expander)?;
+        for (name, value) in std::env::vars_os() {
+            if let (Some(name), Some(value)) = (name.to_str(), value.to_str()) {
+                let flavor = expand::Flavor::Recursive;
+                expander.assign(name, value, flavor, expand::Origin::Environment)?;
+                expander.export(name);
+            }
+        }
+        for variable in &self.variables {
+            if let Some((name, value, operator)) = assignment(variable) {
+                expander.apply(name, value, operator, expand::Origin::CommandLine)?;
+                expander.export(name);
+            }
+        }
+        if let Some(shell) = &self.shell {
+            let flavor = expand::Flavor::Simple;
+            expander.assign("SHELL", shell, flavor, expand::Origin::CommandLine)?;
+        }
+        recursive::define_variables(&mut expander, self)?;
+        Ok(expander)
+    }
+
+    /// Parse the command line arguments (without the program name).
+    /// A leading subcommand name selects the [Command].
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options = Self::default();
+        let mut args = args.peekable();
+        match args.peek().map(String::as_str) {
+            Some("sbom") => options.command = Command::Sbom,
+            Some("graph") => options.command = Command::Graph,
+            Some("verify") => options.command = Command::Verify,
+            Some("outputs") => options.command = Command::Outputs,
+            Some("plan") => options.command = Command::Plan,
+            Some("restart") => options.command = Command::Restart,
+            Some("state") => options.command = Command::State,
+            Some("completions") => options.command = Command::Completions,
+            _ => {}
+        }
+        if options.command != Command::Build {
+            args.next();
+        }
+        // The flags of an outer make-rs come first, so the real ones override them.
+        let mut args = recursive::inherited().into_iter().chain(args).peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--provenance" => options.provenance = true,
+                "--sign" => options.sign = true,
+                "--manifest" => options.manifest = true,
+                "--explain" => options.explain = true,
+                "--trace" => {
+                    options.explain = true;
+                    options.trace = true;
+                }
+                "--strict" => options.strict = true,
+                "--list" => options.list = true,
+                "--category" => options.category = args.next(),
+                "--format" => options.list_format = args.next(),
+                "--" => {
+                    options.args = args.by_ref().collect();
+                    break;
+                }
+                "--flatten" => options.flatten = true,
+                "--compiler-cache" => options.compiler_cache = Some("auto".to_string()),
+                "--error-format=json" => options.json_errors = true,
+                "--error-format=text" => options.json_errors = false,
+                "--debug=expansion" => options.debug_expansion = Some(Vec::new()),
+                "--debug" => options.debug.push(debug::Category::Basic),
+                "--min-free-space" => options.min_free_space = args.next(),
+                "--log-json" => options.log_json = args.next(),
+                "--retry-failed" => options.retry_failed = true,
+                "--exclude" => options.exclude.extend(args.next()),
+                "-f" | "--file" | "--makefile" => options.files.extend(args.next()),
+                "-C" | "--directory" => options.directories.extend(args.next()),
+                "--shell" => options.shell = args.next(),
+                "-s" | "--silent" | "--quiet" => options.silent = true,
+                "-k" | "--keep-going" => options.keep_going = true,
+                "-O" | "--output-sync" => options.output_sync = capture::Mode::Target,
+                "-B" | "--always-make" => options.always_make = true,
+                "-r" | "--no-builtin-rules" => options.no_builtin_rules = true,
+                "--watch" => options.watch = true,
+                "--hash" => options.hash = true,
+                "--profile" => options.profile = Some(String::new()),
+                "-q" | "--question" => options.question = true,
+                "-W" | "--what-if" | "--new-file" | "--assume-new" => {
+                    options.what_if.extend(args.next())
+                }
+                "-o" | "--old-file" | "--assume-old" => options.assume_old.extend(args.next()),
+                "-t" | "--touch" => options.touch = true,
+                "-h" | "--help" => options.command = Command::Help,
+                "-v" | "--version" => options.command = Command::Version,
+                // The number of jobs is optional, so only a number is taken.
+                "-j" | "--jobs" => {
+                    let count = args.next_if(|next| next.parse::<usize>().is_ok());
+                    options.jobs = Some(count.unwrap_or_default());
+                }
+                _ => {
+                    if let Some(auth) = arg
+                        .strip_prefix("--jobserver-auth=")
+                        .or_else(|| arg.strip_prefix("--jobserver-fds="))
+                    {
+                        options.jobserver = Some(auth.to_string());
+                    } else if let Some(mode) = arg
+                        .strip_prefix("--output-sync=")
+                        .or_else(|| arg.strip_prefix("-O"))
+                    {
+                        let Some(mode) = capture::Mode::from_name(mode) else {
+                            diagnostic::error(format!("unknown output sync mode '{}'", mode));
+                            return Err(Box::new(MakeError::UsageError));
+                        };
+                        options.output_sync = mode;
+                    } else if let Some(tool) = arg.strip_prefix("--compiler-cache=") {
+                        options.compiler_cache = Some(tool.to_string());
+                    } else if let Some(pattern) = arg.strip_prefix("--exclude=") {
+                        options.exclude.push(pattern.to_string());
+                    } else if let Some(file) = arg
+                        .strip_prefix("--file=")
+                        .or_else(|| arg.strip_prefix("--makefile="))
+                        .or_else(|| arg.strip_prefix("-f"))
+                    {
+                        options.files.push(file.to_string());
+                    } else if let Some(directory) = arg
+                        .strip_prefix("--directory=")
+                        .or_else(|| arg.strip_prefix("-C"))
+                    {
+                        options.directories.push(directory.to_string());
+                    } else if let Some(count) = arg
+                        .strip_prefix("--jobs=")
+                        .or_else(|| arg.strip_prefix("-j"))
+                    {
+                        options.jobs = Some(count.to_string());
+                    } else if let Some(file) = arg
+                        .strip_prefix("--what-if=")
+                        .or_else(|| arg.strip_prefix("-W"))
+                    {
+                        options.what_if.push(file.to_string());
+                    } else if let Some(file) = arg
+                        .strip_prefix("--old-file=")
+                        .or_else(|| arg.strip_prefix("--assume-old="))
+                        .or_else(|| arg.strip_prefix("-o"))
+                    {
+                        options.assume_old.push(file.to_string());
+                    } else if let Some(path) = arg.strip_prefix("--log-json=") {
+                        options.log_json = Some(path.to_string());
+                    } else if let Some(trace) = arg.strip_prefix("--profile=") {
+                        options.profile = Some(trace.to_string());
+                    } else if let Some(format) = arg.strip_prefix("--format=") {
+                        options.list_format = Some(format.to_string());
+                    } else if let Some(shell) = arg.strip_prefix("--shell=") {
+                        options.shell = Some(shell.to_string());
+                    } else if let Some(size) = arg.strip_prefix("--min-free-space=") {
+                        options.min_free_space = Some(size.to_string());
+                    } else if let Some(names) = arg.strip_prefix("--debug=expansion=") {
+                        options.debug_expansion =
+                            Some(names.split(',').map(str::to_string).collect());
+                    } else if let Some(list) = arg.strip_prefix("--debug=") {
+                        // `expansion` may be listed along with the categories.
+                        let (expansion, categories): (Vec<&str>, Vec<&str>) =
+                            list.split(',').partition(|name| *name == "expansion");
+                        if !expansion.is_empty() {
+                            options.debug_expansion.get_or_insert_with(Vec::new);
+                        }
+                        match debug::parse(&categories.join(",")) {
+                            Ok(categories) => options.debug.extend(categories),
+                            Err(name) => {
+                                diagnostic::error(format!("unknown debug category '{}'", name));
+                                return Err(Box::new(MakeError::UsageError));
+                            }
+                        }
+                    } else if arg.starts_with('-') {
+                        diagnostic::error(format!(
+                            "unknown option '{}', see 'make-rs --help'",
+                            arg
+                        ));
+                        return Err(Box::new(MakeError::UsageError));
+                    } else if assignment(&arg).is_some() {
+                        options.variables.push(arg);
+                    } else {
+                        options.goals.push(arg);
+                    }
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// A Target's dependency. Can be another [Target], a file,
+/// a URL that is downloaded into the cache or a git repository
+/// at a given revision.
+pub enum Dependency<'a> {
+    Target(&'a Target),
+    File(&'a str),
+    Url(&'a str),
+    /// The repository URL and the revision.
+    Git(&'a str, &'a str),
+}
+
+/// Domain-specific errors that can happen when
+/// parsing or executing a Makefile.
+#[derive(Debug)]
+pub enum MakeError {
+    DependencyDoesNotExist,
+    NoTargets,
+    LineIsNotATarget,
+    BuildError,
+    NoSuchTarget,
+    ChecksumMismatch,
+    DownloadFailed,
+    PackageNotFound,
+    InvalidFunctionArgument,
+    PluginError,
+    ScriptError,
+    InvalidConfig,
+    SigningFailed,
+    VerificationFailed,
+    DeprecatedTarget,
+    UsageError,
+    Cancelled,
+    CircularDependency,
+    ErrorFunction,
+    /// `-q` found a target that is out of date.
+    OutOfDate,
+}
+
+impl std::fmt::Display for MakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let message = match self {
+            MakeError::DependencyDoesNotExist => "a prerequisite does not exist",
+            MakeError::NoTargets => "no targets",
+            MakeError::LineIsNotATarget => "line is not a target",
+            MakeError::BuildError => "build failed",
+            MakeError::NoSuchTarget => "no such target",
+            MakeError::ChecksumMismatch => "checksum mismatch",
+            MakeError::DownloadFailed => "download failed",
+            MakeError::PackageNotFound => "package not found",
+            MakeError::InvalidFunctionArgument => "invalid function argument",
+            MakeError::PluginError => "plugin failed",
+            MakeError::ScriptError => "script failed",
+            MakeError::InvalidConfig => "invalid configuration",
+            MakeError::SigningFailed => "signing failed",
+            MakeError::VerificationFailed => "verification failed",
+            MakeError::DeprecatedTarget => "target is deprecated",
+            MakeError::UsageError => "invalid usage",
+            MakeError::Cancelled => "interrupted",
+            MakeError::CircularDependency => "circular dependency",
+            MakeError::ErrorFunction => "$(error) was called",
+            MakeError::OutOfDate => "a target is out of date",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for MakeError {}
+
+/// A single make target with a name,
+/// dependencies and a list of commands.
+/// Dependencies are strings because graphs
+/// are difficult in Rust.
+#[derive(Debug, Default, Clone)]
+pub struct Target {
+    name: String,
+    dependencies: Vec<String>,
+    /// Prerequisites after a `|`, which are made first but never
+    /// make this target out of date.
+    order_only: Vec<String>,
+    commands: Vec<String>,
+    /// Arguments for `cargo build` if this target is delegated
+    /// to cargo with the `.CARGO` attribute.
+    cargo: Option<Vec<String>>,
+    /// Intermediate targets are deleted after the build if they were created by it.
+    intermediate: bool,
+    /// Stamp targets are tracked by a stamp file instead of a file named like the target.
+    stamp: bool,
+    /// The message of a `.DEPRECATED` target, telling users what to use instead.
+    deprecated: Option<String>,
+    /// The section this target is listed in by `--list`.
+    category: Option<String>,
+    /// The workspace member this target belongs to. Its recipe runs there.
+    directory: Option<String>,
+    /// Whether `.NODEDUP` exempts this target from command deduplication.
+    no_dedup: bool,
+    /// Service targets are started in the background instead of being waited for.
+    service: bool,
+    /// `.PHONY` targets don't stand for a file and are always remade.
+    phony: bool,
+    /// The part of the name matched by `%` if this target was made from a pattern rule.
+    stem: Option<String>,
+    /// All targets of the grouped rule (`a b &: c`) this target comes from,
+    /// which one run of the recipe makes together. Empty for other rules.
+    group: Vec<String>,
+    /// The prerequisites with a `.WAIT` before them, which are only started
+    /// once the prerequisites before them are made.
+    waits: Vec<String>,
+    /// With `.NOTPARALLEL`, the prerequisites are made one after the other.
+    not_parallel: bool,
+    /// `.PRECIOUS` targets are kept when the build is interrupted while
+    /// they are made.
+    precious: bool,
+    /// Whether this is one of the built-in rules, which the Makefile's
+    /// own rules take precedence over.
+    builtin: bool,
+}
+
+/// What running a recipe did.
+#[derive(Debug, Default)]
+struct Ran {
+    /// The commands as they were executed.
+    commands: Vec<String>,
+    /// The CPU time the commands used, if it is known.
+    cpu: Option<std::time::Duration>,
+}
+
+/// What the prefixes of a recipe line ask for.
+#[derive(Debug, Default)]
+struct Prefixes {
+    /// `@`: don't echo the command.
+    silent: bool,
+    /// `-`: carry on with the recipe if the command fails.
+    ignore_errors: bool,
+}
+
+impl Prefixes {
+    /// Split a recipe line into its prefixes and the command.
+    fn strip(line: &str) -> (Self, &str) {
+        let mut prefixes = Self::default();
+        let mut command = line.trim_start();
+        loop {
+            match command.chars().next() {
+                Some('@') => prefixes.silent = true,
+                Some('-') => prefixes.ignore_errors = true,
+                _ => return (prefixes, command),
+            }
+            command = command[1..].trim_start();
+        }
+    }
+}
+
+/// Directory that stamp files are kept in.
+const STAMP_DIR: &str = ".make-rs/stamps";
+
+impl Target {
+    /// The name of the target, which is usually the file it makes.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The normal prerequisites, as written in the Makefile.
+    pub fn dependencies(&self) -> &[String] {
+        &self.dependencies
+    }
+
+    /// The order-only prerequisites, as written in the Makefile.
+    pub fn order_only(&self) -> &[String] {
+        &self.order_only
+    }
+
+    /// The lines of the recipe, before expansion.
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// The normal and order-only prerequisites, which are all made before this target.
+    fn prerequisites(&self) -> impl DoubleEndedIterator<Item = &String> {
+        self.dependencies.iter().chain(&self.order_only)
+    }
+
+    /// The file whose existence and timestamp stand for this target.
+    fn file(&self) -> String {
+        if self.stamp {
+            format!("{}/{}", STAMP_DIR, self.name.replace('/', "_"))
+        } else {
+            // `member//target` is the file `member/target`.
+            self.name.replace("//", "/")
+        }
+    }
+
+    /// Build this target by running its `commands`, the expanded recipe.
+    /// Assumes that dependencies have already been built and are valid.
+    /// `automatic` holds the automatic variables (like `FETCHED`)
+    /// that are passed to the commands in their environment.
+    /// For targets of the build (as opposed to lifecycle hooks), `inputs`
+    /// holds the prerequisite files: compiler invocations are run through
+    /// the compiler cache, and commands that another target already ran in
+    /// the same directory on the same inputs are skipped.
+    fn make(
+        &self,
+        makefile: &Makefile,
+        expander: &expand::Expander,
+        commands: &[String],
+        automatic: &[(&str, String)],
+        inputs: Option<&[String]>,
+    ) -> Result<Ran, Box<dyn std::error::Error>> {
+        let dedup = inputs.map(|inputs| (&makefile.ran, inputs));
+        let shell = shell::Shell::from_variables(expander)?;
+        let mut outcome = Ran::default();
+        let mut keys = Vec::new();
+        // Only recipes running at the same time need their output kept apart.
+        let sync = match makefile.jobs {
+            1 => capture::Mode::None,
+            _ => makefile.options.output_sync,
+        };
+        let mut output = capture::Buffer::default();
+        // Lines of multi-line variables become separate commands.
+        let mut lines: Vec<&str> = commands.iter().flat_map(|c| recipe_lines(c)).collect();
+        // With `.ONESHELL`, the recipe is one script, whose prefixes are those
+        // of its first line. The prefixes of the other lines are dropped.
+        let script;
+        if let (true, Some(first)) = (makefile.oneshell, lines.first()) {
+            let prefix = &first[..first.len() - Prefixes::strip(first).1.len()];
+            let body: Vec<&str> = lines.iter().map(|line| Prefixes::strip(line).1).collect();
+            script = format!("{}{}", prefix, body.join("\n"));
+            lines = vec![&script];
+        }
+        for command in lines {
+            let (prefixes, command) = Prefixes::strip(command);
+            let mut command = command.to_string();
+            let compiler_cache = makefile.compiler_cache.filter(|_| inputs.is_some());
+            if let Some(wrapped) = compiler_cache.and_then(|tool| tool.wrap(&command)) {
+                command = wrapped;
+            }
+            let key = dedup.filter(|_| !self.no_dedup).map(|(_, inputs)| {
+                format!(
+                    "{}\0{}\0{}",
+                    self.directory.as_deref().unwrap_or("."),
+                    inputs.join(" "),
+                    command
+                )
+            });
+            if let (Some((ran, _)), Some(key)) = (dedup, &key) {
+                if ran.lock().unwrap().contains(key) {
+                    println!("make-rs: '{}' already ran, skipping", command);
+                    outcome.commands.push(command);
+                    continue;
+                }
+            }
+            if (!prefixes.silent && !makefile.options.silent) || makefile.options.trace {
+                match sync {
+                    capture::Mode::None => println!("{}", command),
+                    _ => output.echo(&command),
+                }
+            }
+
+            // Execute the command in a shell process.
+            let mut process = shell.command(&command);
+            process.envs(expander.environment(automatic)?);
+            process.env("MAKELEVEL", recursive::child_level());
+            if let Some(directory) = &self.directory {
+                process.current_dir(directory);
+            }
+            let exit = match sync {
+                capture::Mode::None => makefile.engine.run(&mut process)?,
+                _ => output.run(&makefile.engine, &mut process)?,
+            };
+            let status = exit.status;
+            if let Some(cpu) = exit.cpu {
+                *outcome.cpu.get_or_insert_default() += cpu;
+            }
+            if sync == capture::Mode::Line {
+                output.flush();
+            }
+            makefile.engine.emit(engine::Event::CommandFinished {
+                target: &self.name,
+                command: &command,
+                exit_code: status.code(),
+            });
+            outcome.commands.push(command);
+            // Output on stderr (like compiler warnings) doesn't matter,
+            // only the exit status tells whether the command failed.
+            if !status.success() {
+                let status = match status.code() {
+                    Some(code) => format!("exit code {}", code),
+                    None => match process::exit_signal(&status) {
+                        Some(signal) => format!("signal {}", signal),
+                        None => "unknown status".to_string(),
+                    },
+                };
+                // The output comes before the message about it.
+                output.flush();
+                if prefixes.ignore_errors {
+                    diagnostic::warning(format!(
+                        "recipe for '{}' failed ({}), ignored",
+                        self.name, status
+                    ));
+                    continue;
+                }
+                diagnostic::error(format!("recipe for '{}' failed ({})", self.name, status));
+                if inputs.is_some() {
+                    makefile.fail(&self.name, format!("recipe failed ({})", status));
+                }
+                return Err(Box::new(MakeError::BuildError));
+            }
+            keys.extend(key);
+        }
+
+        // Only commands of other targets are skipped, so a recipe
+        // may run the same command repeatedly.
+        if let Some((ran, _)) = dedup {
+            ran.lock().unwrap().extend(keys);
+        }
+        Ok(outcome)
+    }
+}
+
+impl Makefile {
+    /// Parse the Makefile `text`, with the variables of the environment
+    /// and the built-in rules. Included files are read relative to the
+    /// current directory.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let sources = [builtin::rules(), ("Makefile".to_string(), text.to_string())];
+        let fs = std::sync::Arc::new(vfs::Os);
+        Self::from_sources(fs, "", Options::default().expander()?, &sources)
+    }
+
+    /// Parse the Makefile `text` like [Makefile::parse], but read included
+    /// files from `fs` and check and update targets in it.
+    pub fn with_fs(text: &str, fs: Box<dyn vfs::Fs>) -> Result<Self, Box<dyn std::error::Error>> {
+        let sources = [builtin::rules(), ("Makefile".to_string(), text.to_string())];
+        Self::from_sources(fs.into(), "", Options::default().expander()?, &sources)
+    }
+
+    /// Parse the Makefile from `sources`, pairs of file names and contents
+    /// that are read one after the other. Included files are read from `fs`,
+    /// relative to `directory` (the current directory if empty).
+    /// `expander` holds the variables defined before the Makefile is read.
+    fn from_sources(
+        fs: std::sync::Arc<dyn vfs::Fs>,
+        directory: &str,
+        mut expander: expand::Expander,
+        sources: &[(String, String)],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut targets = Vec::new();
+        let mut cargo_targets = Vec::new();
+        let mut attributes = Vec::new();
+        let mut deprecations = Vec::new();
+        let mut categories = Vec::new();
+        let mut hooks = Vec::new();
+        let mut plugin_rules = Vec::new();
+        let mut suffixes: Vec<String> = SUFFIXES.iter().map(|s| s.to_string()).collect();
+        let mut search_path = vpath::SearchPath::default();
+        let mut oneshell = false;
+        let mut not_parallel = false;
+        expander.set_fs(fs.clone());
+
+        // First, we split the input into lines, join continued ones
+        // and filter out the empty ones and comments.
+        // Lines keep their file and number for diagnostics.
+        let mut lines = source::Lines::new(sources)?;
+        // Whether the line before was a rule, to recognize recipes indented with spaces.
+        let mut in_rule = false;
+
+        while let Some(source) = lines.next(&expander)? {
+            expander.locate(Some(&source));
+            let line = source.text.as_str();
+            let after_rule = std::mem::take(&mut in_rule);
+            // `function name = program` defines a function implemented by an executable.
+            if let Some(definition) = line.strip_prefix("function ") {
+                let Some((name, program)) = definition.split_once('=') else {
+                    diagnostic::error_in(&source, "", "expected 'function name = program'");
+                    return Err(Box::new(MakeError::LineIsNotATarget));
+                };
+                expander.define_external(name.trim(), program.trim());
+                continue;
+            }
+
+            // `load plugin.so` loads a plugin, which may register
+            // functions, rules and hooks.
+            if let Some(path) = line.strip_prefix("load ") {
+                let registry = plugin::load(path.trim())?;
+                for (name, function) in registry.functions {
+                    expander.define_plugin(&name, function);
+                }
+                plugin_rules.extend(registry.rules);
+                hooks.extend(registry.hooks);
+                continue;
+            }
+
+            // `vpath pattern dirs` adds directories the prerequisites
+            // matching the pattern are searched in.
+            let vpath = line
+                .strip_prefix("vpath")
+                .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+                .filter(|_| assignment(line).is_none());
+            if let Some(args) = vpath {
+                search_path.directive(&expander.expand_rule(args)?);
+                continue;
+            }
+
+            // `include file...` reads other Makefiles in place of the line.
+            // With `-include` (or `sinclude`), missing files are skipped.
+            let include = match line.split_once(' ') {
+                Some(("include", files)) => Some((files, false)),
+                Some(("-include" | "sinclude", files)) => Some((files, true)),
+                _ => None,
+            };
+            if let Some((files, optional)) = include {
+                let files = expander.expand_rule(files)?;
+                // Lines are read from the front, so the files are included last to first.
+                for path in files.split_whitespace().rev() {
+                    let path = match directory {
+                        "" => path.to_string(),
+                        _ if path.starts_with('/') => path.to_string(),
+                        directory => format!("{}/{}", directory, path),
+                    };
+                    if optional && !fs.exists(&path) {
+                        continue;
+                    }
+                    lines.include(&*fs, &path, &source)?;
+                }
+                continue;
+            }
+
+            // `define NAME` up to `endef` defines a variable with a multi-line value,
+            // which source::read put after the first line.
+            let define = line.trim_start().strip_prefix("define");
+            if let Some(rest) =
+                define.filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+            {
+                let (header, body) = rest.split_once('\n').unwrap_or((rest, ""));
+                let header = header.trim();
+                let (name, operator) = match header.strip_suffix('=') {
+                    Some(name) => operator(name),
+                    None => (header, expand::Operator::Set(expand::Flavor::Recursive)),
+                };
+                let name = expander.expand_rule(name)?;
+                if name.trim().is_empty() {
+                    diagnostic::error_in(&source, "define", "empty variable name");
+                    return Err(Box::new(MakeError::LineIsNotATarget));
+                }
+                expander.apply(name.trim(), body, operator, expand::Origin::File)?;
+                continue;
+            }
+
+            // `export NAME...` passes variables to recipes in their environment,
+            // `export NAME = value` also defines it and `export` alone exports all.
+            if line.trim_end() == "export" {
+                expander.export_all();
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix("export ") {
+                match assignment(rest) {
+                    Some((name, value, operator)) => {
+                        expander.apply(name, value, operator, expand::Origin::File)?;
+                        expander.export(name);
+                    }
+                    None => {
+                        for name in expander.expand_rule(rest)?.split_whitespace() {
+                            expander.export(name);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // `NAME = value` defines a variable. Its value is expanded when it is used.
+            // With `NAME := value`, it is expanded once right here instead.
+            if let Some((name, value, operator)) = assignment(line) {
+                expander.apply(name, value, operator, expand::Origin::File)?;
+                continue;
+            }
+
+            // Otherwise, we assume that the line is a target (otherwise the Makefile is invalid).
+            // Targets and prerequisites are expanded right away, so they
+            // only see the variables defined above them.
+            let line = expander.expand_rule(line)?;
+            // Lines like `$(info ...)` expand to nothing.
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some((target, dependencies)) = line.split_once(':') else {
+                if after_rule && source.text.starts_with(' ') {
+                    let prefix = match expander.recipe_prefix() {
+                        '\t' => "a tab".to_string(),
+                        prefix => format!("'{}'", prefix),
+                    };
+                    let message = format!("recipe line must start with {} (found spaces)", prefix);
+                    diagnostic::error_in(&source, "", message);
+                    return Err(Box::new(MakeError::LineIsNotATarget));
+                }
+                diagnostic::error_in(
+                    &source,
+                    "",
+                    "missing ':' in rule or '=' in variable definition",
+                );
+                return Err(Box::new(MakeError::LineIsNotATarget));
+            };
+
+            // If we found a target, we manually advance the `lines` iterator
+            // until a line that doesn't start with the recipe prefix
+            // (i.e. a line without commands) is reached.
+            let mut commands = Vec::new();
+            while let Some(line) = lines.peek(&expander)? {
+                match line.text.strip_prefix(expander.recipe_prefix()) {
+                    Some(command) => {
+                        commands.push(command.trim().to_string());
+                        let _ = lines.next(&expander)?;
+                    }
+                    None => break,
+                }
+            }
+            in_rule = true;
+
+            // `.CARGO: names = args` delegates the named targets to cargo.
+            // It is applied once all targets are known.
+            if target == ".CARGO" {
+                let (names, args) = dependencies.split_once('=').unwrap_or((dependencies, ""));
+                cargo_targets.push((names.to_owned(), args.to_owned()));
+                continue;
+            }
+
+            // `.DEPRECATED: names = message` marks the named targets as deprecated.
+            if target == ".DEPRECATED" {
+                let (names, message) = dependencies.split_once('=').unwrap_or((dependencies, ""));
+                deprecations.push((names.to_owned(), message.trim().to_owned()));
+                continue;
+            }
+
+            // `.CATEGORY: category = names` groups targets for `--list`.
+            if target == ".CATEGORY" {
+                let (category, names) = dependencies.split_once('=').unwrap_or(("", dependencies));
+                categories.push((category.trim().to_owned(), names.to_owned()));
+                continue;
+            }
+
+            // `.SUFFIXES: .x .y` adds suffixes for suffix rules, `.SUFFIXES:` alone
+            // removes all of them.
+            if target == ".SUFFIXES" {
+                match dependencies.trim() {
+                    "" => suffixes.clear(),
+                    added => suffixes.extend(added.split_whitespace().map(str::to_string)),
+                }
+                continue;
+            }
+
+            // `.ONESHELL:` runs each recipe in a single shell.
+            if target == ".ONESHELL" {
+                oneshell = true;
+                continue;
+            }
+
+            // `.NOTPARALLEL:` alone runs the whole build serially.
+            if target == ".NOTPARALLEL" && dependencies.trim().is_empty() {
+                not_parallel = true;
+                continue;
+            }
+
+            // Likewise for attributes of targets listed as prerequisites.
+            if [
+                ".NOTPARALLEL",
+                ".PRECIOUS",
+                ".INTERMEDIATE",
+                ".NOTINTERMEDIATE",
+                ".STAMP",
+                ".NODEDUP",
+                ".SERVICE",
+                ".PHONY",
+            ]
+            .contains(&target)
+            {
+                attributes.push((target.to_owned(), dependencies.to_owned()));
+                continue;
+            }
+
+            // `targets: %.o: %.c` is a static pattern rule, which makes each
+            // of the targets with the stem substituted in the prerequisites.
+            let static_pattern = dependencies
+                .split_once(':')
+                .filter(|(pattern, _)| pattern.trim().contains('%'))
+                .filter(|(pattern, _)| pattern.split_whitespace().count() == 1);
+            if let Some((pattern, dependencies)) = static_pattern {
+                let pattern = pattern.trim();
+                let (dependencies, order_only) =
+                    dependencies.split_once('|').unwrap_or((dependencies, ""));
+                for name in target.split_whitespace() {
+                    let Some(stem) = pattern::stem(pattern, name) else {
+                        diagnostic::error_in(
+                            &source,
+                            "",
+                            format!(
+                                "target '{}' doesn't match the target pattern '{}'",
+                                name, pattern
+                            ),
+                        );
+                        return Err(Box::new(MakeError::LineIsNotATarget));
+                    };
+                    let substitute = |deps: &str| -> Vec<String> {
+                        deps.split_whitespace()
+                            .map(|dep| dep.replacen('%', stem, 1))
+                            .collect()
+                    };
+                    let mut dependencies = substitute(dependencies);
+                    let mut order_only = substitute(order_only);
+                    let mut waits = take_waits(&mut dependencies);
+                    waits.extend(take_waits(&mut order_only));
+                    targets.push(Target {
+                        name: name.to_owned(),
+                        dependencies,
+                        order_only,
+                        commands: commands.clone(),
+                        stem: Some(stem.to_owned()),
+                        waits,
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+
+            // Prerequisites after a `|` are order-only.
+            let (dependencies, order_only) =
+                dependencies.split_once('|').unwrap_or((dependencies, ""));
+            let mut dependencies: Vec<String> = dependencies
+                .split_whitespace()
+                .map(|dep| dep.trim().to_string())
+                .collect();
+            let mut order_only: Vec<String> =
+                order_only.split_whitespace().map(str::to_string).collect();
+            let mut waits = take_waits(&mut dependencies);
+            waits.extend(take_waits(&mut order_only));
+
+            // `a b &: c` is a grouped rule: one run of its recipe makes all its targets.
+            if let Some(names) = target.trim_end().strip_suffix('&') {
+                let group: Vec<String> = names.split_whitespace().map(str::to_string).collect();
+                for name in &group {
+                    targets.push(Target {
+                        name: name.clone(),
+                        dependencies: dependencies.clone(),
+                        order_only: order_only.clone(),
+                        commands: commands.clone(),
+                        group: group.clone(),
+                        waits: waits.clone(),
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+            targets.push(Target {
+                name: target.to_owned(),
+                dependencies,
+                order_only,
+                commands,
+                waits,
+                builtin: source.file == builtin::FILE,
+                ..Default::default()
+            })
+        }
+
+        // Suffix rules like `.c.o:` (or `.c:`) are the pattern rules `%.o: %.c`
+        // (or `%: %.c`), if the suffixes are known. With prerequisites,
+        // they are ordinary targets.
+        for target in &mut targets {
+            if !target.dependencies.is_empty() {
+                continue;
+            }
+            if let Some((from, to)) = suffix_rule(&target.name, &suffixes) {
+                target.dependencies = vec![format!("%{}", from)];
+                target.name = format!("%{}", to);
+            }
+        }
+
+        // Rules from plugins are added last, so they never become the default goal.
+        for (name, dependencies, recipe) in plugin_rules {
+            targets.push(Target {
+                name,
+                dependencies: dependencies
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect(),
+                commands: recipe.lines().map(str::to_string).collect(),
+                ..Default::default()
+            });
+        }
+
+        for (names, args) in cargo_targets {
+            for name in names.split_whitespace() {
+                let target = targets
+                    .iter_mut()
+                    .find(|t| t.name == name)
+                    .ok_or(MakeError::NoSuchTarget)?;
+                target.cargo = Some(args.split_whitespace().map(str::to_string).collect());
+            }
+        }
+
+        for (names, message) in deprecations {
+            for name in names.split_whitespace() {
+                let target = targets
+                    .iter_mut()
+                    .find(|t| t.name == name)
+                    .ok_or(MakeError::NoSuchTarget)?;
+                target.deprecated = Some(message.clone());
+            }
+        }
+
+        for (category, names) in categories {
+            for name in names.split_whitespace() {
+                let target = targets
+                    .iter_mut()
+                    .find(|t| t.name == name)
+                    .ok_or(MakeError::NoSuchTarget)?;
+                target.category = Some(category.clone());
+            }
+        }
+
+        // `.NOTINTERMEDIATE` overrides `.INTERMEDIATE` for the listed targets,
+        // or for all targets if it has no prerequisites.
+        let not_intermediate: Vec<&str> = attributes
+            .iter()
+            .filter(|(attribute, _)| attribute == ".NOTINTERMEDIATE")
+            .flat_map(|(_, names)| names.split_whitespace())
+            .collect();
+        let no_intermediates = attributes
+            .iter()
+            .any(|(attribute, names)| attribute == ".NOTINTERMEDIATE" && names.trim().is_empty());
+        for (attribute, names) in &attributes {
+            for name in names.split_whitespace() {
+                let Some(target) = targets.iter_mut().find(|t| t.name == name) else {
+                    // Like in GNU make, phony and precious targets don't need a rule.
+                    if attribute == ".PHONY" || attribute == ".PRECIOUS" {
+                        continue;
+                    }
+                    return Err(Box::new(MakeError::NoSuchTarget));
+                };
+                match attribute.as_str() {
+                    ".INTERMEDIATE" => {
+                        target.intermediate = !no_intermediates && !not_intermediate.contains(&name)
+                    }
+                    ".STAMP" => target.stamp = true,
+                    ".NODEDUP" => target.no_dedup = true,
+                    ".SERVICE" => target.service = true,
+                    ".PHONY" => target.phony = true,
+                    ".NOTPARALLEL" => target.not_parallel = true,
+                    ".PRECIOUS" => target.precious = true,
+                    _ => {}
+                }
+            }
+        }
+
+        if expander.origin("VPATH").is_some() {
+            search_path.set_vpath(&expander.expand_rule("$(VPATH)")?);
+        }
+
+        // Recipes are expanded later, where no line is being parsed.
+        expander.locate(None);
+        Ok(Self {
+            targets,
+            expander,
+            hooks,
+            engine: Default::default(),
+            failed: Default::default(),
+            built: Default::default(),
+            intermediates: Default::default(),
+            fs,
+            made: Default::default(),
+            ran: Default::default(),
+            compiler_cache: None,
+            services: Default::default(),
+            timings: Default::default(),
+            fingerprints: Default::default(),
+            hashed: Default::default(),
+            min_free_space: None,
+            jobs: 1,
+            jobserver: None,
+            members: Default::default(),
+            search_path,
+            oneshell,
+            not_parallel,
+            options: Options::default(),
+        })
+    }
+
+    /// All targets, including pattern rules, in the order of the Makefile.
+    pub fn targets(&self) -> &[Target] {
+        &self.targets
+    }
+
+    /// The target called `name`, if there is one.
+    pub fn target(&self, name: &str) -> Option<&Target> {
+        self.targets.iter().find(|t| t.name == name)
+    }
+
+    /// Build `target` and its prerequisites if they are out of date.
+    pub fn build(&mut self, target: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let goals = [target.to_string()];
+        pattern::instantiate(self, &goals);
+        self.check_cycles(&goals)?;
+        self.validate(&goals)?;
+        self.build_goals(&goals)
+    }
+
+    /// Call `observer` for the [Event]s of the following builds.
+    pub fn observe(&mut self, observer: impl Fn(&Event) + Send + Sync + 'static) {
+        self.engine.observe(Box::new(observer));
+    }
+
+    /// A token that cancels the builds of this Makefile, also from other
+    /// threads: running recipe commands are stopped, no further targets
+    /// are started, and [Makefile::build] fails with [MakeError::Cancelled].
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.engine.token()
+    }
+
+    /// Find out what kind of dependency `dep` is. Dependencies may carry
+    /// an expected checksum, which is split off and returned along with the name.
+    pub fn dependency<'a>(&'a self, dep: &'a str) -> (Dependency<'a>, &'a str, Option<&'a str>) {
+        let (name, checksum) = split_checksum(dep);
+        let dep = match self.targets.iter().find(|t| t.name == name) {
+            Some(target) => Dependency::Target(target),
+            None if fetch::is_url(name) => Dependency::Url(name),
+            None => match fetch::split_git(name) {
+                Some((url, rev)) => Dependency::Git(url, rev),
+                None => Dependency::File(name),
+            },
+        };
+        (dep, name, checksum)
+    }
+
+    /// All targets `goal` depends on (including itself), each listed
+    /// once and after its own dependencies.
+    pub fn resolve(&self, goal: &str) -> Result<Vec<&Target>, MakeError> {
+        fn visit<'a>(
+            makefile: &'a Makefile,
+            target: &'a Target,
+            visited: &mut std::collections::HashSet<&'a str>,
+            order: &mut Vec<&'a Target>,
+        ) {
+            if !visited.insert(&target.name) {
+                return;
+            }
+            for dep in target.prerequisites() {
+                if let (Dependency::Target(t), _, _) = makefile.dependency(dep) {
+                    visit(makefile, t, visited, order);
+                }
+            }
+            order.push(target);
+        }
+
+        let target = self
+            .targets
+            .iter()
+            .find(|t| t.name == goal)
+            .ok_or(MakeError::NoSuchTarget)?;
+        let mut order = Vec::new();
+        visit(self, target, &mut Default::default(), &mut order);
+        Ok(order)
+    }
+
+    // Build the target with name `target` including dependencies, if it
+    // is out of date. Returns whether any recipe ran for it.
+    // Each target is made at most once per build, however many
+    // targets depend on it.
+    fn make(&self, target: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(&made) = self.made.lock().unwrap().get(target) {
+            return made.ok_or_else(|| Box::new(MakeError::BuildError).into());
+        }
+        let result = self.make_once(target);
+        self.record_made(target, result.as_ref().ok().copied());
+        result
+    }
+
+    /// Remember that the target `name` was made, with whether a recipe ran
+    /// for it (`None` if it failed). The recipe of a grouped rule made
+    /// the other targets of the group as well.
+    fn record_made(&self, name: &str, ran: Option<bool>) {
+        let mut made = self.made.lock().unwrap();
+        if let Some(target) = self.target(name) {
+            for member in &target.group {
+                made.insert(member.clone(), ran);
+            }
+        }
+        made.insert(name.to_string(), ran);
+    }
+
+    /// Make `target` and its prerequisites for [Makefile::make].
+    fn make_once(&self, target: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(target) = self.prepare(target)? else {
+            return Ok(false);
+        };
+        let mut made = std::collections::HashMap::new();
+        let mut failure = None;
+        for dep in target.prerequisites() {
+            if let (Dependency::Target(t), _, _) = self.dependency(dep) {
+                match self.make(&t.name) {
+                    Ok(ran) => {
+                        made.insert(t.name.as_str(), ran);
+                    }
+                    Err(err) if self.keep_going() => {
+                        failure.get_or_insert(err);
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        if let Some(err) = failure {
+            diagnostic::error(format!(
+                "target '{}' not remade because of errors",
+                target.name
+            ));
+            return Err(err);
+        }
+        self.remake(target, &made)
+    }
+
+    /// Whether to carry on with other targets after one failed, with `-k`.
+    fn keep_going(&self) -> bool {
+        self.options.keep_going && self.engine.check().is_ok()
+    }
+
+    /// Look up the target `name` before making it. Returns `None` for
+    /// excluded targets, which are not built, and neither are their
+    /// prerequisites unless another target needs them.
+    fn prepare(&self, name: &str) -> Result<Option<&Target>, Box<dyn std::error::Error>> {
+        debug::log(debug::Category::Basic, || {
+            format!("considering target '{}'", name)
+        });
+        let Some(target) = self.targets.iter().find(|t| t.name == name) else {
+            diagnostic::error(format!("no rule to make target '{}'", name));
+            return Err(Box::new(MakeError::NoSuchTarget));
+        };
+
+        if self.assumed_old(target) {
+            debug::log(debug::Category::Basic, || {
+                format!("target '{}' is assumed old, not remaking it", name)
+            });
+            return Ok(None);
+        }
+        if self.excluded(target) {
+            println!("make-rs: skipping excluded target '{}'", target.name);
+            return Ok(None);
+        }
+
+        if let Some(message) = &target.deprecated {
+            if self.options.strict {
+                diagnostic::error(format!(
+                    "target '{}' is deprecated: {}",
+                    target.name, message
+                ));
+                return Err(Box::new(MakeError::DeprecatedTarget));
+            }
+            diagnostic::warning(format!(
+                "target '{}' is deprecated: {}",
+                target.name, message
+            ));
+        }
+        Ok(Some(target))
+    }
+
+    /// Remake `target` if it is out of date, once the targets among its
+    /// prerequisites were made. `made` tells whether a recipe ran for each
+    /// of them. Returns whether any recipe ran for `target`.
+    fn remake(
+        &self,
+        target: &Target,
+        made: &std::collections::HashMap<&str, bool>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let result = self.remake_target(target, made);
+        if let Err(err) = &result {
+            self.fail(&target.name, err.to_string());
+        }
+        result
+    }
+
+    /// Remember that `target` failed and why, unless it failed already.
+    fn fail(&self, target: &str, why: String) {
+        let mut failed = self.failed.lock().unwrap();
+        if !failed.iter().any(|(name, _)| name == target) {
+            failed.push((target.to_string(), why));
+        }
+    }
+
+    /// The targets that failed during this build.
+    fn failed_targets(&self) -> Vec<String> {
+        let failed = self.failed.lock().unwrap();
+        failed.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// [Makefile::remake] without remembering failures.
+    fn remake_target(
+        &self,
+        target: &Target,
+        made: &std::collections::HashMap<&str, bool>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        // Find all the dependencies and see if they are targets or required files.
+        // Order-only prerequisites are flagged, they must only exist.
+        let deps = (target.dependencies.iter().map(|dep| (dep, false)))
+            .chain(target.order_only.iter().map(|dep| (dep, true)))
+            .map(|(dep, order_only)| (self.dependency(dep), order_only));
+
+        // Then check if the files exist or download them.
+        let mut fetched = Vec::new();
+        let mut checkouts = Vec::new();
+        let mut inputs = Vec::new();
+        let mut order_only_inputs = Vec::new();
+        let mut prerequisites_ran = false;
+        let mut remade_without_file = None;
+        for ((dep, name, checksum), order_only) in deps {
+            let mut path = name.to_string();
+            debug::log(debug::Category::Verbose, || match dep {
+                Dependency::Target(t) => match made.get(t.name.as_str()) {
+                    Some(true) => {
+                        format!("prerequisite '{}' of '{}' was remade", t.name, target.name)
+                    }
+                    _ => format!(
+                        "prerequisite '{}' of '{}' was not remade",
+                        t.name, target.name
+                    ),
+                },
+                _ => format!("checking prerequisite '{}' of '{}'", name, target.name),
+            });
+            match dep {
+                Dependency::Target(_) if order_only => {}
+                Dependency::Target(t) => {
+                    // Like in GNU make, a target without a file (like a phony
+                    // one or `FORCE`) counts as newer than its dependents.
+                    let without_file = (t.phony || !self.fs.exists(&t.file()))
+                        && !self.excluded(t)
+                        && !self.assumed_old(t);
+                    if without_file || made.get(t.name.as_str()).copied().unwrap_or(false) {
+                        prerequisites_ran = true;
+                    }
+                    if without_file {
+                        remade_without_file.get_or_insert_with(|| t.name.clone());
+                    }
+                }
+                Dependency::File(f) => {
+                    let Some(found) = self.locate(f) else {
+                        diagnostic::error(format!(
+                            "'{}', needed by '{}', does not exist",
+                            f, target.name
+                        ));
+                        return Err(Box::new(MakeError::DependencyDoesNotExist));
+                    };
+                    if found != f {
+                        debug::log(debug::Category::Verbose, || {
+                            format!("found prerequisite '{}' as '{}'", f, found)
+                        });
+                    }
+                    path = found;
+                }
+                Dependency::Url(url) => {
+                    path = fetch::fetch(&*self.fs, url)?.to_string_lossy().into_owned();
+                    fetched.push(path.clone());
+                }
+                Dependency::Git(url, rev) => {
+                    path = fetch::checkout(&*self.fs, url, rev)?
+                        .to_string_lossy()
+                        .into_owned();
+                    checkouts.push(path.clone());
+                }
+            }
+            if let Some(expected) = checksum {
+                verify_checksum(&*self.fs, &path, expected)?;
+            }
+            if order_only {
+                order_only_inputs.push(path);
+            } else {
+                inputs.push(path);
+            }
+        }
+        let expander = self.expander_of(target);
+        let automatic = self.automatic(target, &inputs, &order_only_inputs, &fetched, &checkouts);
+
+        // Services run in the background, unless they are running already.
+        // With `-t`, nothing is run.
+        if target.service && self.options.touch {
+            return Ok(false);
+        }
+        if target.service {
+            if let Some(pid) = service::running(&target.name) {
+                println!(
+                    "make-rs: service '{}' is already running (pid {})",
+                    target.name, pid
+                );
+                return Ok(false);
+            }
+            let script = target
+                .commands
+                .iter()
+                .map(|command| {
+                    let command = expander.expand(command, &automatic)?;
+                    Ok(Prefixes::strip(&command).1.to_string())
+                })
+                .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?
+                .join("\n");
+            let shell = shell::Shell::from_variables(expander)?;
+            let directory = target.directory.as_deref();
+            let child = service::start(&target.name, &shell, &script, directory)?;
+            self.services
+                .lock()
+                .unwrap()
+                .push((target.name.clone(), child));
+            return Ok(true);
+        }
+
+        let file = target.file();
+        let existed = self.fs.exists(&file);
+        let modified = self.fs.modified(&file).ok();
+        // With `--hash`, the recipe is expanded before deciding whether it
+        // runs, since the fingerprint covers the commands as they run.
+        let recipe = (self.options.hash)
+            .then(|| self.recipe(target, &automatic))
+            .transpose()?;
+        let fingerprint = (recipe.as_deref())
+            .map(|commands| fingerprint::Fingerprint::new(&*self.fs, commands, &inputs));
+        let mut reason = (target.phony.then_some(explain::Reason::Phony))
+            .or_else(|| self.out_of_date(target, &inputs, fingerprint.as_ref()))
+            .or(remade_without_file.map(explain::Reason::RemadePrerequisite))
+            .or(self
+                .options
+                .always_make
+                .then_some(explain::Reason::AlwaysMake));
+        // Cargo targets are also remade if cargo rebuilt anything.
+        if let (Some(args), false) = (&target.cargo, self.options.touch) {
+            if cargo::build(args)? && reason.is_none() {
+                reason = Some(explain::Reason::CargoRebuilt);
+            }
+        }
+        if let (None, Some(fingerprint)) = (&reason, &fingerprint) {
+            self.hashed
+                .lock()
+                .unwrap()
+                .push((target.name.clone(), fingerprint.clone()));
+        }
+        // Targets without a recipe have nothing to do of their own.
+        let idle = target.commands.is_empty() && target.cargo.is_none();
+        let Some(reason) = reason.filter(|_| !idle || prerequisites_ran) else {
+            debug::log(debug::Category::Basic, || {
+                format!("no need to remake target '{}'", target.name)
+            });
+            self.engine.emit(engine::Event::TargetUpToDate {
+                target: &target.name,
+            });
+            return Ok(prerequisites_ran);
+        };
+        debug::log(debug::Category::Basic, || {
+            format!("must remake target '{}': {}", target.name, reason)
+        });
+        if self.options.explain {
+            println!("make-rs: remaking '{}' because {}", target.name, reason);
+        }
+
+        // With `-t`, the target is touched instead of remade.
+        if self.options.touch {
+            if !target.phony {
+                if !self.options.silent {
+                    println!("touch {}", file);
+                }
+                self.fs.touch(&file)?;
+            }
+            return Ok(true);
+        }
+
+        if let Some(minimum) = self.min_free_space {
+            space::wait_for(minimum);
+        }
+
+        self.engine.check()?;
+        self.engine.emit(engine::Event::TargetStarted {
+            target: &target.name,
+        });
+        // With a jobserver, the recipe runs in one of its job slots.
+        let token = match &self.jobserver {
+            Some(jobserver) => Some(jobserver.acquire()?),
+            None => None,
+        };
+        let started = std::time::SystemTime::now();
+        let result = recipe
+            .map_or_else(|| self.recipe(target, &automatic), Ok)
+            .and_then(|commands| target.make(self, expander, &commands, &automatic, Some(&inputs)));
+        drop(token);
+        self.engine.emit(engine::Event::TargetFinished {
+            target: &target.name,
+            success: result.is_ok(),
+        });
+        // A target the interrupted recipe already changed is likely
+        // incomplete, so it is deleted unless it is precious.
+        let keep = target.phony || target.precious || target.stamp;
+        let changed = self.fs.exists(&file) && self.fs.modified(&file).ok() != modified;
+        if result.is_err() && self.engine.check().is_err() && !keep && changed {
+            diagnostic::warning(format!("deleting file '{}'", file));
+            self.fs.remove(&file)?;
+        }
+        if result.is_err() {
+            self.run_hook(
+                ".ON_TARGET_FAILURE",
+                &[("FAILED_TARGET", target.name.clone())],
+            )?;
+        }
+        let ran = result?;
+        self.built.lock().unwrap().push(target.name.clone());
+        if let Ok(duration) = started.elapsed() {
+            self.timings.lock().unwrap().push(profile::Timing {
+                target: target.name.clone(),
+                started,
+                duration,
+                cpu: ran.cpu,
+            });
+        }
+
+        if target.stamp {
+            self.fs.write(&file, b"")?;
+        }
+        if let Some(fingerprint) = fingerprint {
+            self.hashed
+                .lock()
+                .unwrap()
+                .push((target.name.clone(), fingerprint));
+        }
+        if target.intermediate && !existed && self.fs.exists(&file) {
+            self.intermediates.lock().unwrap().push(file);
+        }
+
+        if self.options.provenance {
+            provenance::Provenance {
+                target: &target.name,
+                inputs: &inputs,
+                commands: &ran.commands,
+                started,
+                finished: std::time::SystemTime::now(),
+            }
+            .write(&*self.fs)?;
+        }
+
+        Ok(true)
+    }
+
+    /// The expander of the Makefile that `target` is defined in.
+    fn expander_of(&self, target: &Target) -> &expand::Expander {
+        match &target.directory {
+            Some(member) => &self.members[member],
+            None => &self.expander,
+        }
+    }
+
+    /// The automatic variables of the recipe of `target`, with the
+    /// prerequisite files `inputs` and `order_only`, of which `fetched`
+    /// were downloaded and `checkouts` are git checkouts.
+    fn automatic(
+        &self,
+        target: &Target,
+        inputs: &[String],
+        order_only: &[String],
+        fetched: &[String],
+        checkouts: &[String],
+    ) -> Vec<(&'static str, String)> {
+        // Recipes of workspace members run in the member's directory,
+        // so they see names relative to it.
+        let local = |name: &str| match &target.directory {
+            Some(dir) => name
+                .strip_prefix(&format!("{}//", dir))
+                .or_else(|| name.strip_prefix(&format!("{}/", dir)))
+                .unwrap_or(name)
+                .to_string(),
+            None => name.to_string(),
+        };
+        let mut prerequisites: Vec<String> = Vec::new();
+        for input in inputs {
+            let input = local(input);
+            if !prerequisites.contains(&input) {
+                prerequisites.push(input);
+            }
+        }
+        let mut automatic = vec![
+            ("@", local(&target.name)),
+            ("<", prerequisites.first().cloned().unwrap_or_default()),
+            ("^", prerequisites.join(" ")),
+            (
+                "|",
+                order_only
+                    .iter()
+                    .map(|input| local(input))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            ("*", target.stem.clone().unwrap_or_default()),
+        ];
+        // Only targets with URL prerequisites see `FETCHED`, others may
+        // have a variable of that name.
+        if !fetched.is_empty() {
+            automatic.push(("FETCHED", fetched.join(" ")));
+        }
+        // Likewise, `CHECKOUT` is only bound for git prerequisites.
+        if !checkouts.is_empty() {
+            automatic.push(("CHECKOUT", checkouts.join(" ")));
+        }
+        // Without arguments after `--`, `ARGS` may come from the command
+        // line or the Makefile like any other variable.
+        if !self.options.args.is_empty() {
+            automatic.push(("ARGS", self.args()));
+        }
+        automatic
+    }
+
+    /// The recipe of `target` with references expanded, like in GNU make
+    /// the whole recipe is before it runs.
+    fn recipe(
+        &self,
+        target: &Target,
+        automatic: &[(&str, String)],
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let expander = self.expander_of(target);
+        (target.commands.iter())
+            .map(|command| expander.expand(command, automatic))
+            .collect()
+    }
+
+    /// Whether and why `target` with the prerequisite files `inputs` is out
+    /// of date. With `--hash`, targets that were built before are compared
+    /// by `fingerprint`, all others by modification times.
+    fn out_of_date(
+        &self,
+        target: &Target,
+        inputs: &[String],
+        fingerprint: Option<&fingerprint::Fingerprint>,
+    ) -> Option<explain::Reason> {
+        let file = target.file();
+        match (fingerprint, self.fingerprints.get(&target.name)) {
+            (Some(now), Some(before)) if self.fs.exists(&file) => now.changes(before),
+            // A grouped target is out of date if any target of its group is.
+            _ => explain::reason(&*self.fs, &file, inputs).or_else(|| {
+                (target.group.iter())
+                    .filter(|member| **member != target.name)
+                    .find_map(|member| {
+                        let file = member.replace("//", "/");
+                        explain::reason(&*self.fs, &file, inputs)
+                    })
+            }),
+        }
+    }
+
+    /// Where the file prerequisite `name` is: itself if it exists,
+    /// otherwise the first match in the `vpath` search path.
+    fn locate(&self, name: &str) -> Option<String> {
+        self.search_path.find(&*self.fs, name)
+    }
+
+    /// Whether `target` was given with `-o`, so it is not remade.
+    fn assumed_old(&self, target: &Target) -> bool {
+        self.options.assume_old.contains(&target.file())
+    }
+
+    /// Whether `target` matches one of the `--exclude` patterns.
+    fn excluded(&self, target: &Target) -> bool {
+        self.options
+            .exclude
+            .iter()
+            .any(|pattern| glob::matches(pattern, &target.name))
+    }
+
+    /// Fail if a target needed for `goals` or the lifecycle hooks depends
+    /// on itself, reporting the path of the cycle.
+    fn check_cycles(&self, goals: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        fn visit<'a>(
+            makefile: &'a Makefile,
+            target: &'a Target,
+            path: &mut Vec<&'a str>,
+            done: &mut std::collections::HashSet<&'a str>,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            if let Some(start) = path.iter().position(|name| *name == target.name) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(&target.name);
+                diagnostic::error(format!("circular dependency: {}", cycle.join(" -> ")));
+                return Err(Box::new(MakeError::CircularDependency));
+            }
+            if !done.insert(&target.name) {
+                return Ok(());
+            }
+            path.push(&target.name);
+            for dep in target.prerequisites() {
+                if let (Dependency::Target(t), _, _) = makefile.dependency(dep) {
+                    visit(makefile, t, path, done)?;
+                }
+            }
+            path.pop();
+            Ok(())
+        }
+
+        let mut done = std::collections::HashSet::new();
+        let roots = goals.iter().map(String::as_str).chain(LIFECYCLE_HOOKS);
+        for root in roots {
+            if let Some(target) = self.targets.iter().find(|t| t.name == root) {
+                visit(self, target, &mut Vec::new(), &mut done)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that every file prerequisite needed for `goals` either exists
+    /// or has a rule, reporting all missing ones at once before anything runs.
+    fn validate(&self, goals: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut missing = Vec::new();
+        let mut visited = std::collections::HashSet::new();
+        let mut pending: std::collections::VecDeque<&str> =
+            goals.iter().map(String::as_str).collect();
+        while let Some(name) = pending.pop_front() {
+            let Some(target) = self.targets.iter().find(|t| t.name == name) else {
+                continue;
+            };
+            if !visited.insert(name) || self.excluded(target) || self.assumed_old(target) {
+                continue;
+            }
+            for dep in target.prerequisites() {
+                match self.dependency(dep) {
+                    (Dependency::Target(t), _, _) => pending.push_back(&t.name),
+                    (Dependency::File(f), _, _) if self.locate(f).is_none() => {
+                        missing.push((f, &target.name));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+        diagnostic::error(match missing.len() {
+            1 => "1 prerequisite does not exist and has no rule to make it".to_string(),
+            n => format!(
+                "{} prerequisites do not exist and have no rule to make them",
+                n
+            ),
+        });
+        for (file, target) in missing {
+            diagnostic::error(format!("'{}', needed by '{}'", file, target));
+        }
+        Err(Box::new(MakeError::DependencyDoesNotExist))
+    }
+
+    /// Build all `goals` in order, running the build start and end hooks around them.
+    fn build_goals(&self, goals: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        self.run_hook(".ON_BUILD_START", &[])?;
+        let result = if self.jobs > 1 {
+            schedule::build(self, goals, self.jobs).map(|made| {
+                for (goal, made) in goals.iter().zip(made) {
+                    if !made {
+                        self.report_up_to_date(goal);
+                    }
+                }
+            })
+        } else {
+            let mut result = Ok(());
+            for goal in goals {
+                match self.make(goal) {
+                    Ok(false) => self.report_up_to_date(goal),
+                    Ok(true) => {}
+                    Err(err) if self.keep_going() => {
+                        if result.is_ok() {
+                            result = Err(err);
+                        }
+                    }
+                    Err(err) => {
+                        result = Err(err);
+                        break;
+                    }
+                }
+            }
+            result
+        };
+        // With `-k`, the failures are summed up at the end.
+        if result.is_err() && self.options.keep_going {
+            let failed = self.failed.lock().unwrap();
+            if !failed.is_empty() {
+                let count = match failed.len() {
+                    1 => "1 target".to_string(),
+                    n => format!("{} targets", n),
+                };
+                diagnostic::error(format!("{} failed:", count));
+                for (target, why) in failed.iter() {
+                    diagnostic::error(format!("  '{}': {}", target, why));
+                }
+            }
+        }
+
+        // Intermediate files are only needed while building.
+        for file in self.intermediates.lock().unwrap().drain(..) {
+            if !self.options.silent {
+                println!("rm {}", file);
+            }
+            self.fs.remove(&file)?;
+        }
+        // A cancelled build stops right away.
+        if self.engine.check().is_ok() {
+            let status = if result.is_ok() { "success" } else { "failure" };
+            self.run_hook(".ON_BUILD_END", &[("BUILD_RESULT", status.to_string())])?;
+        }
+        result
+    }
+
+    /// Tell the user that nothing had to be done for `goal`.
+    fn report_up_to_date(&self, goal: &str) {
+        if self.options.silent {
+            return;
+        }
+        let has_recipe = self
+            .targets
+            .iter()
+            .any(|t| t.name == goal && !t.commands.is_empty());
+        if has_recipe {
+            println!("make-rs: '{}' is up to date.", goal);
+        } else {
+            println!("make-rs: Nothing to be done for '{}'.", goal);
+        }
+    }
+
+    /// Run the lifecycle hook `name` if the Makefile defines it.
+    /// Its prerequisites are built like targets, then its recipe
+    /// runs with the given automatic variables.
+    fn run_hook(
+        &self,
+        name: &str,
+        automatic: &[(&str, String)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(hook) = self.targets.iter().find(|t| t.name == name) else {
+            return Ok(());
+        };
+        for dep in hook.prerequisites() {
+            self.make(dep)?;
+        }
+        let commands = self.recipe(hook, automatic)?;
+        hook.make(self, &self.expander, &commands, automatic, None)?;
+        Ok(())
+    }
+
+    /// The pass-through arguments, quoted for the shell.
+    fn args(&self) -> String {
+        self.options
+            .args
+            .iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The target built when no goals are given: the one named by
+    /// `.DEFAULT_GOAL`, otherwise the first one that is neither a pattern
+    /// rule nor special. Names starting with `.` are special, unless they
+    /// contain a `/` (like `./app`).
+    pub fn default_goal(&self) -> Result<String, Box<dyn std::error::Error>> {
+        if self.expander.origin(".DEFAULT_GOAL").is_some() {
+            let goal = self.expander.expand_rule("$(.DEFAULT_GOAL)")?;
+            match goal.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => {}
+                [goal] => return Ok(goal.to_string()),
+                _ => {
+                    diagnostic::error(format!(
+                        ".DEFAULT_GOAL contains more than one target: {}",
+                        goal.trim()
+                    ));
+                    return Err(Box::new(MakeError::UsageError));
+                }
+            }
+        }
+        let special = |name: &str| name.starts_with('.') && !name.contains('/');
+        let goal = self
+            .targets
+            .iter()
+            .map(|t| t.name.as_str())
+            .find(|name| !special(name) && !name.contains('%'))
+            .ok_or(MakeError::NoTargets)?;
+        Ok(goal.to_string())
+    }
+}
+
+/// The suffixes known for suffix rules before `.SUFFIXES` changes them,
+/// as in GNU make.
+const SUFFIXES: [&str; 36] = [
+    ".out", ".a", ".ln", ".o", ".c", ".cc", ".C", ".cpp", ".p", ".f", ".F", ".m", ".r", ".y", ".l",
+    ".ym", ".yl", ".s", ".S", ".mod", ".sym", ".def", ".h", ".info", ".dvi", ".tex", ".texinfo",
+    ".texi", ".txinfo", ".w", ".ch", ".web", ".sh", ".elc", ".el", ".cxx",
+];
+
+/// Split the name of a suffix rule into the source and target suffix,
+/// which is empty for single-suffix rules like `.c:`.
+fn suffix_rule<'a>(name: &'a str, suffixes: &[String]) -> Option<(&'a str, &'a str)> {
+    suffixes.iter().find_map(|from| {
+        let to = name.strip_prefix(from.as_str())?;
+        (to.is_empty() || suffixes.iter().any(|s| s == to)).then(|| (&name[..from.len()], to))
+    })
+}
+
+/// Take the `.WAIT` markers out of `prerequisites`, returning the
+/// prerequisites that come right after one.
+fn take_waits(prerequisites: &mut Vec<String>) -> Vec<String> {
+    let mut waits = Vec::new();
+    let mut after_wait = false;
+    prerequisites.retain(|prerequisite| {
+        if prerequisite == ".WAIT" {
+            after_wait = true;
+            return false;
+        }
+        if std::mem::take(&mut after_wait) {
+            waits.push(prerequisite.clone());
+        }
+        true
+    });
+    waits
+}
+
+/// Special targets that are run at points of the build instead of being built.
+const LIFECYCLE_HOOKS: [&str; 3] = [".ON_BUILD_START", ".ON_TARGET_FAILURE", ".ON_BUILD_END"];
+
+/// Quote `arg` for `sh` if it contains anything but safe characters.
+fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Split a dependency of the form `file@sha256:<hex>` into
+/// the file name and the expected checksum.
+fn split_checksum(dep: &str) -> (&str, Option<&str>) {
+    match dep.rsplit_once("@sha256:") {
+        Some((name, checksum)) => (name, Some(checksum)),
+        None => (dep, None),
+    }
+}
+
+/// Check that the contents of `file` hash to the `expected` sha256.
+/// Dependencies with a rule (e.g. a download) have already been
+/// rebuilt at this point, so a mismatch is always an error.
+fn verify_checksum(
+    fs: &dyn vfs::Fs,
+    file: &str,
+    expected: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let actual = hash::to_hex(&hash::sha256(&fs.read(file)?));
+    if !actual.eq_ignore_ascii_case(expected) {
+        diagnostic::error(format!(
+            "checksum mismatch for {}: expected {}, got {}",
+            file, expected, actual
+        ));
+        return Err(Box::new(MakeError::ChecksumMismatch));
+    }
+    Ok(())
+}
+
+/// Split an expanded recipe line into commands at newlines,
+/// except those escaped with a backslash.
+fn recipe_lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, _) in text.match_indices('\n') {
+        if !text[..i].ends_with('\\') {
+            lines.push(&text[start..i]);
+            start = i + 1;
+        }
+    }
+    lines.push(&text[start..]);
+    lines
+}
+
+/// Split a variable assignment like `NAME = value`, `NAME := value`,
+/// `NAME ::= value`, `NAME += value` or `NAME ?= value` into the name,
+/// the value and the operator.
+fn assignment(line: &str) -> Option<(&str, &str, expand::Operator)> {
+    let (name, value) = line.split_once('=')?;
+    let (name, operator) = operator(name);
+    // A colon left in the name makes it a rule like `.CARGO: app = ...`.
+    if name.contains(':') {
+        return None;
+    }
+    Some((name.trim(), value.trim(), operator))
+}
+
+/// Split the operator off the part of an assignment before the `=`.
+fn operator(name: &str) -> (&str, expand::Operator) {
+    if let Some(name) = name.strip_suffix("::").or_else(|| name.strip_suffix(':')) {
+        (name, expand::Operator::Set(expand::Flavor::Simple))
+    } else if let Some(name) = name.strip_suffix('+') {
+        (name, expand::Operator::Append)
+    } else if let Some(name) = name.strip_suffix('?') {
+        (name, expand::Operator::Default)
+    } else {
+        (name, expand::Operator::Set(expand::Flavor::Recursive))
+    }
+}
+
+/// Names of the Makefile that is read if none is given with `-f`,
+/// in the order they are looked for.
+const MAKEFILES: [&str; 3] = ["GNUmakefile", "makefile", "Makefile"];
+
+/// Find the Makefile in `dir` (the current directory if empty).
+fn find_makefile(fs: &dyn vfs::Fs, dir: &str) -> Option<String> {
+    MAKEFILES
+        .iter()
+        .map(|name| match dir {
+            "" => name.to_string(),
+            dir => format!("{}/{}", dir, name),
+        })
+        .find(|path| fs.exists(path))
+}
+
+/// Read the Makefile `path` for [Makefile::from_sources].
+fn read_makefile(
+    fs: &dyn vfs::Fs,
+    path: &str,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let data = fs.read_to_string(path).map_err(|err| {
+        diagnostic::error(format!("cannot read {}: {}", path, err));
+        err
+    })?;
+    Ok((path.to_string(), data))
+}
+
+/// Run make-rs with the command line arguments `args` (without the program name).
+/// Errors exit the process with status 2, like in GNU make.
+pub fn cli(args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let result = Options::parse(args).and_then(|options| {
+        if options.json_errors {
+            diagnostic::use_json();
+        }
+        debug::enable(&options.debug);
+        if let Some(path) = &options.log_json {
+            eventlog::open(path)?;
+        }
+        run(options)
+    });
+    if let Err(err) = &result {
+        if let Some(MakeError::OutOfDate) = err.downcast_ref() {
+            std::process::exit(1);
+        }
+        let reported = diagnostic::report(err.as_ref());
+        // Like a shell, an interrupted make-rs exits with 128 plus the signal.
+        if let Some(signal) = engine::interrupt_signal() {
+            std::process::exit(128 + signal);
+        }
+        if !reported {
+            eprintln!("make-rs: {}", err);
+        }
+        std::process::exit(2);
+    }
+    result
+}
+
+fn run(mut options: Options) -> Result<(), Box<dyn std::error::Error>> {
+    match options.command {
+        Command::Help => {
+            print!("{}", USAGE);
+            return Ok(());
+        }
+        Command::Version => {
+            println!("make-rs {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Command::Completions => {
+            let shell = options.goals.first().map_or("", String::as_str);
+            print!("{}", completions::script(shell)?);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // `-C` changes the directory before anything is read.
+    if !options.directories.is_empty() {
+        let directory: std::path::PathBuf = options.directories.drain(..).collect();
+        std::env::set_current_dir(&directory).map_err(|err| {
+            diagnostic::error(format!(
+                "cannot change to directory {}: {}",
+                directory.display(),
+                err
+            ));
+            err
+        })?;
+        let directory = std::env::current_dir()?;
+        let silent = options.silent;
+        if !silent {
+            println!("make-rs: Entering directory '{}'", directory.display());
+        }
+        let result = run(options);
+        if !silent {
+            println!("make-rs: Leaving directory '{}'", directory.display());
+        }
+        return result;
+    }
+
+    // The state can be restored before there is anything to parse.
+    if options.command == Command::State {
+        return state::run(&options.goals);
+    }
+
+    if options.watch {
+        return watch::run(options);
+    }
+    build(options, &mut Vec::new())
+}
+
+/// Parse the Makefile and do what `options` ask for, which is usually
+/// building the goals. The files the build reads (the Makefiles and file
+/// prerequisites) are added to `watched`.
+fn build(options: Options, watched: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    // Find and parse the Makefile.
+    let config = config::Config::load()?;
+    let members = config.get_list("workspace.members");
+    // Makefiles given with `-f` are read in order, otherwise the first one
+    // found is used. A workspace does not need a Makefile of its own.
+    let mut fs: Box<dyn vfs::Fs> = Box::new(vfs::Os);
+    if !options.what_if.is_empty() {
+        fs = Box::new(vfs::WhatIf::new(fs, options.what_if.clone()));
+    }
+    if !options.assume_old.is_empty() {
+        fs = Box::new(vfs::AssumeOld::new(fs, options.assume_old.clone()));
+    }
+    let paths = match &options.files[..] {
+        [] => Vec::from_iter(find_makefile(&*fs, "")),
+        files => files.to_vec(),
+    };
+    watched.extend(paths.iter().cloned());
+    if paths.is_empty() && members.is_empty() {
+        diagnostic::error("no Makefile found");
+        return Err(Box::new(MakeError::NoTargets));
+    }
+    let mut sources = paths
+        .iter()
+        .map(|path| read_makefile(&*fs, path))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !options.no_builtin_rules {
+        sources.insert(0, builtin::rules());
+    }
+    let mut makefile = Makefile::from_sources(fs.into(), "", options.expander()?, &sources)?;
+    makefile.options = options;
+    workspace::load(&mut makefile, &members)?;
+
+    // The project file may override variables of the Makefile.
+    for (name, value) in config.section("defaults.variables") {
+        makefile.expander.define_variable(name, value);
+    }
+    // `.FEATURES = hash` in the Makefile asks for `--hash`, too.
+    if makefile.expander.origin(".FEATURES").is_some() {
+        let features = makefile.expander.expand_rule("$(.FEATURES)")?;
+        makefile.options.hash |= features.split_whitespace().any(|f| f == "hash");
+    }
+    if makefile.options.hash {
+        makefile.fingerprints = fingerprint::read(&*makefile.fs)?;
+    }
+    if let Some(size) = &makefile.options.min_free_space {
+        makefile.min_free_space = Some(space::parse_size(size)?);
+    }
+    // `-j` without a number uses all CPUs.
+    match makefile
+        .options
+        .jobs
+        .as_deref()
+        .or(config.get("defaults.jobs"))
+    {
+        Some("") => {
+            makefile.jobs = std::thread::available_parallelism().map_or(1, |n| n.get());
+        }
+        Some(count) => match count.parse() {
+            Ok(count) if count > 0 => makefile.jobs = count,
+            _ => {
+                diagnostic::error(format!("invalid number of jobs '{}'", count));
+                return Err(Box::new(MakeError::UsageError));
+            }
+        },
+        None => {}
+    }
+    // Recursive invocations still get `-j` with `.NOTPARALLEL`, like in GNU make.
+    if makefile.not_parallel {
+        makefile.jobs = 1;
+    }
+    // Recursive invocations share the job slots through the jobserver
+    // of the outermost make, which is created for `-j`.
+    if let Some(auth) = makefile.options.jobserver.take() {
+        match jobserver::Client::open(&auth) {
+            Some(client) => makefile.jobserver = Some(client),
+            None => {
+                diagnostic::warning("jobserver unavailable: using -j1");
+                makefile.jobs = 1;
+            }
+        }
+    } else if makefile.jobs > 1 {
+        match jobserver::Client::create(makefile.jobs) {
+            Ok(client) => makefile.jobserver = Some(client),
+            // Without a jobserver, recursive invocations have their own job slots.
+            Err(err) if err.kind() == std::io::ErrorKind::Unsupported => {}
+            Err(err) => return Err(Box::new(err)),
+        }
+    }
+    if let Some(client) = &makefile.jobserver {
+        makefile.options.jobserver = Some(client.auth().to_string());
+        recursive::define_flags(&mut makefile.expander, &makefile.options)?;
+        for expander in makefile.members.values_mut() {
+            recursive::define_flags(expander, &makefile.options)?;
+        }
+    }
+    if let Some(variables) = &makefile.options.debug_expansion {
+        makefile.expander.debug(variables.clone());
+        for expander in makefile.members.values_mut() {
+            expander.debug(variables.clone());
+        }
+    }
+    if makefile.options.flatten {
+        workspace::flatten(&mut makefile)?;
+    }
+    if let Some(name) = makefile.options.compiler_cache.as_deref() {
+        makefile.compiler_cache = ccache::Tool::detect(name)?;
+        match makefile.compiler_cache {
+            Some(tool) => println!("make-rs: using {} as compiler cache", tool.program()),
+            None => println!("make-rs: no compiler cache found, compiling without one"),
+        }
+    }
+
+    if makefile.options.command == Command::Verify {
+        return manifest::verify(&*makefile.fs);
+    }
+
+    if makefile.options.list {
+        return Ok(list::print(
+            &makefile,
+            makefile.options.category.as_deref(),
+            makefile.options.list_format.as_deref(),
+        )?);
+    }
+
+    if makefile.options.command == Command::Outputs {
+        if makefile.options.goals.is_empty() {
+            return outputs::list(&*makefile.fs, None);
+        }
+        let mut rules = Vec::new();
+        for goal in &makefile.options.goals {
+            rules.extend(makefile.resolve(goal)?.iter().map(|t| t.name.as_str()));
+        }
+        return outputs::list(&*makefile.fs, Some(&rules));
+    }
+
+    // If there are goals given, build these targets in order.
+    // Otherwise build the first target in the Makefile.
+    let mut goals = makefile.options.goals.clone();
+    if makefile.options.retry_failed {
+        goals.extend(failures::read(&*makefile.fs)?);
+        if goals.is_empty() {
+            println!("make-rs: no targets failed in the last build.");
+            return Ok(());
+        }
+    }
+    if goals.is_empty() {
+        match config.get("defaults.goal") {
+            Some(goal) => goals.push(goal.to_string()),
+            None => goals.push(makefile.default_goal()?),
+        }
+    }
+
+    pattern::instantiate(&mut makefile, &goals);
+    makefile.check_cycles(&goals)?;
+
+    match makefile.options.command {
+        Command::Sbom => {
+            print!("{}", sbom::cyclonedx(&makefile, &goals)?);
+            return Ok(());
+        }
+        Command::Graph => {
+            print!("{}", graph::dot(&makefile, &goals)?);
+            return Ok(());
+        }
+        Command::Plan => return plan::print(&makefile, &goals),
+        _ => {}
+    }
+
+    // `-q` answers with the exit status only. It is returned as an error,
+    // so the jobserver is cleaned up before make-rs exits.
+    if makefile.options.question {
+        if plan::out_of_date(&makefile, &goals)? {
+            return Err(Box::new(MakeError::OutOfDate));
+        }
+        return Ok(());
+    }
+
+    if makefile.options.command == Command::Restart {
+        for goal in &goals {
+            service::stop(goal)?;
+        }
+    }
+
+    makefile.validate(&goals)?;
+
+    // Plugins observe the targets of the build.
+    let hooks = std::mem::take(&mut makefile.hooks);
+    if !hooks.is_empty() {
+        makefile.engine.observe(Box::new(move |event| match event {
+            engine::Event::TargetStarted { target } => {
+                plugin::run_hooks(&hooks, plugin::HookEvent::BeforeTarget, target)
+            }
+            engine::Event::TargetFinished { target, success } => {
+                let event = match success {
+                    true => plugin::HookEvent::TargetSucceeded,
+                    false => plugin::HookEvent::TargetFailed,
+                };
+                plugin::run_hooks(&hooks, event, target)
+            }
+            _ => {}
+        }));
+    }
+    if makefile.options.log_json.is_some() {
+        makefile.engine.observe(Box::new(eventlog::event));
+    }
+    engine::cancel_on_interrupt(makefile.engine.token());
+
+    let start = std::time::Instant::now();
+    let mut result = makefile.build_goals(&goals);
+
+    // Goals without a rule were reported by the build.
+    for targets in goals.iter().filter_map(|goal| makefile.resolve(goal).ok()) {
+        for target in targets {
+            for dep in target.prerequisites() {
+                if let (Dependency::File(file), _, _) = makefile.dependency(dep) {
+                    let file = makefile.locate(file).unwrap_or_else(|| file.to_string());
+                    if !watched.contains(&file) {
+                        watched.push(file);
+                    }
+                }
+            }
+        }
+    }
+
+    // State in .make-rs is only kept for the features that use it.
+    // Remember what the rules that ran produced, if asked to. A grouped
+    // rule produced all targets of its group.
+    if config.get("outputs.track") == Some("true") {
+        let mut produced: Vec<(String, String)> = Vec::new();
+        for name in makefile.built.lock().unwrap().iter() {
+            match makefile.target(name).filter(|t| !t.group.is_empty()) {
+                Some(target) => produced
+                    .extend((target.group.iter()).map(|member| (name.clone(), member.clone()))),
+                None => produced.push((name.clone(), name.clone())),
+            }
+        }
+        outputs::record(&*makefile.fs, &produced)?;
+    }
+    // The durations order the ready queue of parallel builds and weigh its jobs.
+    let timings = makefile.timings.lock().unwrap().clone();
+    if makefile.jobs > 1 || makefile.options.profile.is_some() {
+        let durations: Vec<_> = timings
+            .iter()
+            .map(|timing| {
+                let entry = history::Entry {
+                    duration: timing.duration,
+                    cpu: timing.cpu,
+                };
+                (timing.target.clone(), entry)
+            })
+            .collect();
+        history::record(&*makefile.fs, &durations)?;
+    }
+    fingerprint::record(&*makefile.fs, &makefile.hashed.lock().unwrap())?;
+    // Failures are kept for --retry-failed after -k, and forgotten
+    // once a build succeeds.
+    let failed = makefile.failed_targets();
+    if failed.is_empty() || makefile.options.keep_going || makefile.options.retry_failed {
+        failures::record(&*makefile.fs, &failed)?;
+    }
+
+    if makefile.options.manifest {
+        manifest::record(&*makefile.fs, &makefile.built.lock().unwrap())?;
+    }
+
+    if let Some(trace) = &makefile.options.profile {
+        print!("{}", profile::summary(&timings, start.elapsed()));
+        if !trace.is_empty() {
+            std::fs::write(trace, profile::trace(&timings))?;
+        }
+    }
+
+    // Sign what was built, if asked to.
+    if result.is_ok() && makefile.options.sign {
+        let tool = sign::Tool::from_name(config.get("sign.tool").unwrap_or("minisign"))?;
+        let built = makefile.built.lock().unwrap().clone();
+        result = sign::sign(tool, config.get("sign.key"), &built);
+    }
+
+    // Report the finished build to the configured notifiers.
+    let summary = notify::BuildSummary {
+        goals,
+        success: result.is_ok(),
+        duration: start.elapsed(),
+        failed: makefile.failed_targets(),
+    };
+    if let Some(url) = config.get("notify.webhook") {
+        notify::post(url, &summary.to_json());
+    }
+    #[cfg(feature = "notifiers")]
+    {
+        if let Some(webhook) = config.get("notify.slack.webhook") {
+            notify::slack(webhook, &summary);
+        }
+        if let (Some(homeserver), Some(room), Some(token)) = (
+            config.get("notify.matrix.homeserver"),
+            config.get("notify.matrix.room"),
+            config.get("notify.matrix.token"),
+        ) {
+            notify::matrix(homeserver, room, token, &summary);
+        }
+    }
+
+    // Keep services that were started running until they exit,
+    // unless the build they are part of failed.
+    let services = std::mem::take(&mut *makefile.services.lock().unwrap());
+    if result.is_err() {
+        service::stop_all(services);
+        return result;
+    }
+    service::wait(services)
+}
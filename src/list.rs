@@ -0,0 +1,95 @@
+//! `--list`: the targets of the Makefile, grouped by their `.CATEGORY`,
+//! either for people or, with `--format=json`, for editors and scripts.
+
+use crate::{json, Makefile, Target};
+
+/// Category of targets that are not in any `.CATEGORY`.
+const UNCATEGORIZED: &str = "other";
+
+/// Print the targets grouped by category, in the order the categories
+/// first appear. With `category`, only that category is printed.
+/// `format` is `text` (the default), `long` to add the prerequisites,
+/// or `json`.
+pub fn print(
+    makefile: &Makefile,
+    category: Option<&str>,
+    format: Option<&str>,
+) -> Result<(), crate::MakeError> {
+    let mut categories: Vec<(&str, Vec<&Target>)> = Vec::new();
+    for target in makefile
+        .targets
+        .iter()
+        .filter(|t| !t.name.starts_with('.') && !t.is_pattern())
+    {
+        let name = target.category.as_deref().unwrap_or(UNCATEGORIZED);
+        match categories.iter_mut().find(|(c, _)| *c == name) {
+            Some((_, targets)) => targets.push(target),
+            None => categories.push((name, vec![target])),
+        }
+    }
+    // Uncategorized targets are listed last.
+    categories.sort_by_key(|(name, _)| *name == UNCATEGORIZED);
+
+    if let Some(category) = category {
+        categories.retain(|(name, _)| *name == category);
+        if categories.is_empty() {
+            crate::diagnostic::error(format!("no targets in category '{}'", category));
+            return Err(crate::MakeError::NoTargets);
+        }
+    }
+
+    match format.unwrap_or("text") {
+        "text" => text(&categories, false),
+        "long" => text(&categories, true),
+        "json" => println!("{}", to_json(&categories)),
+        format => {
+            crate::diagnostic::error(format!("unknown list format '{}'", format));
+            return Err(crate::MakeError::UsageError);
+        }
+    }
+    Ok(())
+}
+
+/// Print the categories for people, optionally with the prerequisites.
+fn text(categories: &[(&str, Vec<&Target>)], long: bool) {
+    for (name, targets) in categories {
+        println!("{}:", name);
+        for target in targets {
+            let mut line = format!("  {}", target.name);
+            if long && !target.dependencies.is_empty() {
+                line.push_str(&format!(": {}", target.dependencies.join(" ")));
+            }
+            if target.phony {
+                line.push_str(" (phony)");
+            }
+            if let Some(message) = &target.deprecated {
+                line.push_str(&format!(" (deprecated: {})", message));
+            }
+            println!("{}", line);
+        }
+    }
+}
+
+/// The targets as a JSON array, in the order they are printed as text.
+fn to_json(categories: &[(&str, Vec<&Target>)]) -> String {
+    let targets: Vec<String> = categories
+        .iter()
+        .flat_map(|(category, targets)| targets.iter().map(move |target| (category, target)))
+        .map(|(category, target)| {
+            format!(
+                "{{\"name\":{},\"category\":{},\"prerequisites\":{},\"order_only\":{},\"phony\":{},\"deprecated\":{}}}",
+                json::string(&target.name),
+                json::string(category),
+                json::string_array(&target.dependencies),
+                json::string_array(&target.order_only),
+                target.phony,
+                target
+                    .deprecated
+                    .as_deref()
+                    .map(json::string)
+                    .unwrap_or_else(|| "null".to_string()),
+            )
+        })
+        .collect();
+    format!("[{}]", targets.join(","))
+}
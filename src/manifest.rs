@@ -0,0 +1,98 @@
+//! A manifest of built outputs with their sizes and checksums,
+//! which the tree can later be verified against.
+
+use crate::vfs::Fs;
+use std::collections::BTreeMap;
+
+/// Where the manifest is written.
+pub const MANIFEST_FILE: &str = ".make-rs/manifest";
+
+/// An entry of the manifest: size and sha256 of a file.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    size: u64,
+    sha256: String,
+}
+
+/// Read the manifest, keyed by path. A missing manifest is empty.
+fn read(fs: &dyn Fs) -> Result<BTreeMap<String, Entry>, Box<dyn std::error::Error>> {
+    let data = match fs.read_to_string(MANIFEST_FILE) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(Box::new(err)),
+    };
+
+    // Every line is `sha256 size path`, with the path last so it may contain spaces.
+    let mut entries = BTreeMap::new();
+    for line in data.lines() {
+        let mut fields = line.splitn(3, ' ');
+        if let (Some(sha256), Some(size), Some(path)) =
+            (fields.next(), fields.next(), fields.next())
+        {
+            let entry = Entry {
+                size: size.parse()?,
+                sha256: sha256.to_string(),
+            };
+            entries.insert(path.to_string(), entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// The entry for the file at `path` as it is now, if it exists.
+fn entry(fs: &dyn Fs, path: &str) -> Option<Entry> {
+    let data = fs.read(path).ok()?;
+    Some(Entry {
+        size: data.len() as u64,
+        sha256: crate::functions::sha256_hex(&data),
+    })
+}
+
+/// Record the built `outputs` in the manifest. Entries of
+/// outputs that were not built this time are kept.
+pub fn record(fs: &dyn Fs, outputs: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = read(fs)?;
+    for output in outputs {
+        if let Some(entry) = entry(fs, output) {
+            entries.insert(output.clone(), entry);
+        }
+    }
+
+    let mut data = String::new();
+    for (path, entry) in &entries {
+        data.push_str(&format!("{} {} {}\n", entry.sha256, entry.size, path));
+    }
+    fs.write(MANIFEST_FILE, data.as_bytes())?;
+    Ok(())
+}
+
+/// Check every file in the manifest against the tree and report
+/// those that are missing or changed.
+pub fn verify(fs: &dyn Fs) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = read(fs)?;
+    let mut mismatches = 0;
+    for (path, recorded) in &entries {
+        match entry(fs, path) {
+            None => {
+                println!("MISSING {}", path);
+                mismatches += 1;
+            }
+            Some(actual) if actual != *recorded => {
+                println!("CHANGED {}", path);
+                mismatches += 1;
+            }
+            Some(_) => {}
+        }
+    }
+
+    if mismatches > 0 {
+        crate::diagnostic::error(format!(
+            "{} of {} files do not match the manifest",
+            mismatches,
+            entries.len()
+        ));
+        return Err(Box::new(crate::MakeError::VerificationFailed));
+    }
+    println!("OK {} files", entries.len());
+    Ok(())
+}
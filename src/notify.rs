@@ -0,0 +1,134 @@
+//! Notifications about finished builds.
+
+use crate::json;
+
+/// What happened during a build, for reporting it elsewhere.
+#[derive(Debug)]
+pub struct BuildSummary {
+    pub goals: Vec<String>,
+    pub success: bool,
+    pub duration: std::time::Duration,
+    pub failed: Vec<String>,
+}
+
+impl BuildSummary {
+    /// The summary as a JSON object.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"goals\":{},\"success\":{},\"duration_ms\":{},\"failed_targets\":{}}}",
+            json::string_array(&self.goals),
+            self.success,
+            self.duration.as_millis(),
+            json::string_array(&self.failed)
+        )
+    }
+}
+
+/// POST `body` as JSON to `url`.
+pub fn post(url: &str, body: &str) {
+    send("POST", url, &[], body);
+}
+
+/// Send `body` as JSON to `url` with the HTTP `method` and extra `headers`.
+/// Failing to notify is only reported, as it should not fail an
+/// otherwise finished build.
+fn send(method: &str, url: &str, headers: &[String], body: &str) {
+    use std::io::Write;
+
+    let mut curl = std::process::Command::new("curl");
+    curl.args(["--fail", "--silent", "--show-error", "--request", method])
+        .args(["--header", "Content-Type: application/json"]);
+    for header in headers {
+        curl.arg("--header").arg(header);
+    }
+    let child = curl
+        .args(["--data-binary", "@-", url])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .spawn();
+    let sent = child.and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(body.as_bytes())?;
+        }
+        child.wait()
+    });
+    match sent {
+        Ok(status) if status.success() => {}
+        _ => crate::diagnostic::warning(format!("failed to notify {}", url)),
+    }
+}
+
+/// A one-line, human readable description of the build for chat messages,
+/// naming the machine since builds often run on shared hosts.
+#[cfg(feature = "notifiers")]
+fn message(summary: &BuildSummary) -> String {
+    let host = std::fs::read_to_string("/etc/hostname")
+        .map(|host| host.trim().to_string())
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown host".to_string());
+    let goals = summary.goals.join(" ");
+    let seconds = summary.duration.as_secs_f64();
+    if summary.success {
+        format!("make-rs on {}: built {} in {:.1}s", host, goals, seconds)
+    } else if summary.failed.is_empty() {
+        format!(
+            "make-rs on {}: {} failed after {:.1}s",
+            host, goals, seconds
+        )
+    } else {
+        format!(
+            "make-rs on {}: {} failed after {:.1}s (failed targets: {})",
+            host,
+            goals,
+            seconds,
+            summary.failed.join(", ")
+        )
+    }
+}
+
+/// Post the build result to a Slack incoming webhook.
+#[cfg(feature = "notifiers")]
+pub fn slack(webhook: &str, summary: &BuildSummary) {
+    let body = format!("{{\"text\":{}}}", json::string(&message(summary)));
+    post(webhook, &body);
+}
+
+/// Send the build result as a message to a Matrix room.
+#[cfg(feature = "notifiers")]
+pub fn matrix(homeserver: &str, room: &str, token: &str, summary: &BuildSummary) {
+    // Every message needs a unique transaction id.
+    let txn = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    let url = format!(
+        "{}/_matrix/client/v3/rooms/{}/send/m.room.message/make-rs-{}",
+        homeserver.trim_end_matches('/'),
+        percent_encode(room),
+        txn
+    );
+    let body = format!(
+        "{{\"msgtype\":\"m.text\",\"body\":{}}}",
+        json::string(&message(summary))
+    );
+    send(
+        "PUT",
+        &url,
+        &[format!("Authorization: Bearer {}", token)],
+        &body,
+    );
+}
+
+/// Percent-encode everything but unreserved characters, e.g. for room ids.
+#[cfg(feature = "notifiers")]
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,61 @@
+//! A persistent index of the files produced by each rule.
+
+use crate::vfs::Fs;
+use std::collections::BTreeSet;
+
+/// Where the index is stored.
+const OUTPUTS_FILE: &str = ".make-rs/outputs";
+
+/// Read the index as (rule, output) pairs. A missing index is empty.
+fn read(fs: &dyn Fs) -> Result<BTreeSet<(String, String)>, Box<dyn std::error::Error>> {
+    let data = match fs.read_to_string(OUTPUTS_FILE) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(Box::new(err)),
+    };
+    Ok(data
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(rule, output)| (rule.to_string(), output.to_string()))
+        .collect())
+}
+
+/// Add the files produced by the rules that ran in this build to the index.
+/// `produced` holds (rule, output) pairs; outputs that do not exist
+/// (e.g. of phony targets like `clean`) are left out.
+pub fn record(
+    fs: &dyn Fs,
+    produced: &[(String, String)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut index = read(fs)?;
+    let before = index.len();
+    index.extend(
+        produced
+            .iter()
+            .filter(|(_, output)| fs.exists(output))
+            .cloned(),
+    );
+    if index.len() == before {
+        return Ok(());
+    }
+
+    let mut data = String::new();
+    for (rule, output) in &index {
+        data.push_str(&format!("{}\t{}\n", rule, output));
+    }
+    fs.write(OUTPUTS_FILE, data.as_bytes())?;
+    Ok(())
+}
+
+/// Print all known outputs, or only those of the given `rules`.
+pub fn list(fs: &dyn Fs, rules: Option<&[&str]>) -> Result<(), Box<dyn std::error::Error>> {
+    let outputs: BTreeSet<String> = read(fs)?
+        .into_iter()
+        .filter(|(rule, _)| rules.is_none_or(|rules| rules.contains(&rule.as_str())))
+        .map(|(_, output)| output)
+        .collect();
+    for output in outputs {
+        println!("{}", output);
+    }
+    Ok(())
+}
@@ -0,0 +1,159 @@
+//! Pattern rules like `%.o: %.c`, which can make any target matching
+//! their pattern. Before the build, a concrete target is instantiated
+//! from a pattern rule for every prerequisite or goal that has no
+//! recipe of its own, with the stem substituted for `%` in its
+//! prerequisites. Recipes get the stem as `$*`.
+
+use crate::debug::{self, Category};
+use crate::{Dependency, Makefile, Target};
+use std::collections::{HashSet, VecDeque};
+
+/// How many pattern rules may be chained to make a single prerequisite.
+const MAX_CHAIN: usize = 4;
+
+impl Target {
+    /// Whether this is a pattern rule rather than a concrete target.
+    pub fn is_pattern(&self) -> bool {
+        self.name.contains('%')
+    }
+}
+
+/// Instantiate pattern rules for `goals` and all prerequisites in `makefile`.
+pub fn instantiate(makefile: &mut Makefile, goals: &[String]) {
+    let patterns: Vec<Target> = makefile
+        .targets
+        .iter()
+        .filter(|t| t.is_pattern())
+        .cloned()
+        .collect();
+    if patterns.is_empty() {
+        return;
+    }
+
+    let mut pending: VecDeque<String> = goals.iter().cloned().collect();
+    for target in makefile.targets.iter().filter(|t| !t.is_pattern()) {
+        pending.extend(target.prerequisites().cloned());
+    }
+    let mut seen = HashSet::new();
+    while let Some(dep) = pending.pop_front() {
+        let name = match makefile.dependency(&dep) {
+            (Dependency::Target(t), _, _) if has_recipe(t) => continue,
+            (Dependency::Target(_) | Dependency::File(_), name, _) => name.to_string(),
+            _ => continue,
+        };
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        let Some(instance) = find(makefile, &patterns, &name, 0) else {
+            debug::log(Category::Implicit, || {
+                format!("no pattern rule for '{}'", name)
+            });
+            continue;
+        };
+        debug::log(Category::Implicit, || {
+            format!(
+                "making '{}' with a pattern rule, stem '{}'",
+                name,
+                instance.stem.as_deref().unwrap_or_default()
+            )
+        });
+        pending.extend(instance.prerequisites().cloned());
+
+        // An explicit rule without a recipe only adds prerequisites.
+        match makefile.targets.iter_mut().find(|t| t.name == name) {
+            Some(target) => {
+                let mut dependencies = instance.dependencies;
+                dependencies.append(&mut target.dependencies);
+                target.dependencies = dependencies;
+                let mut order_only = instance.order_only;
+                order_only.append(&mut target.order_only);
+                target.order_only = order_only;
+                target.commands = instance.commands;
+                target.directory = instance.directory;
+                target.stem = instance.stem;
+            }
+            None => makefile.targets.push(instance),
+        }
+    }
+}
+
+/// Find the pattern rule that makes `name`: the one with the shortest stem
+/// among those whose prerequisites exist or can be made. Of rules with
+/// equally long stems, the first one wins, but built-in rules lose to
+/// those of the Makefile.
+fn find(makefile: &Makefile, patterns: &[Target], name: &str, depth: usize) -> Option<Target> {
+    if depth > MAX_CHAIN {
+        return None;
+    }
+    let mut best: Option<Target> = None;
+    for pattern in patterns {
+        // Like in GNU make, rules that match anything (like `%: %.c`)
+        // don't make the prerequisites of other pattern rules, which
+        // would try them for every file.
+        if depth > 0 && pattern.name == "%" {
+            continue;
+        }
+        // `member//%.o` matches the file `member/foo.o`.
+        let Some(stem) = stem(&pattern.name.replace("//", "/"), name) else {
+            continue;
+        };
+        let better = |best: &Target| match best.stem.as_ref().unwrap().len().cmp(&stem.len()) {
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => best.builtin && !pattern.builtin,
+            std::cmp::Ordering::Greater => true,
+        };
+        if best.as_ref().is_some_and(|best| !better(best)) {
+            continue;
+        }
+        debug::log(Category::Implicit, || {
+            format!("trying pattern rule '{}' for '{}'", pattern.name, name)
+        });
+        let substitute = |deps: &[String]| -> Vec<String> {
+            deps.iter().map(|dep| dep.replacen('%', stem, 1)).collect()
+        };
+        let dependencies = substitute(&pattern.dependencies);
+        let order_only = substitute(&pattern.order_only);
+        let missing =
+            dependencies
+                .iter()
+                .chain(&order_only)
+                .find(|dep| match makefile.dependency(dep) {
+                    (Dependency::File(f), _, _) => {
+                        makefile.locate(f).is_none()
+                            && find(makefile, patterns, f, depth + 1).is_none()
+                    }
+                    _ => false,
+                });
+        match missing {
+            Some(missing) => debug::log(Category::Implicit, || {
+                format!(
+                    "rejecting '{}' for '{}': '{}' can't be made",
+                    pattern.name, name, missing
+                )
+            }),
+            None => {
+                best = Some(Target {
+                    name: name.to_string(),
+                    dependencies,
+                    order_only,
+                    stem: Some(stem.to_string()),
+                    ..pattern.clone()
+                })
+            }
+        }
+    }
+    best
+}
+
+/// Whether `target` has a recipe of its own.
+fn has_recipe(target: &Target) -> bool {
+    !target.commands.is_empty() || target.cargo.is_some() || target.is_pattern()
+}
+
+/// The part of `name` matched by the `%` of `pattern`, if it matches.
+pub fn stem<'a>(pattern: &str, name: &'a str) -> Option<&'a str> {
+    let (prefix, suffix) = pattern.split_once('%')?;
+    name.strip_prefix(prefix)?
+        .strip_suffix(suffix)
+        .filter(|stem| !stem.is_empty())
+}
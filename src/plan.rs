@@ -0,0 +1,210 @@
+//! `make-rs plan`: what a build would do and why, without running anything.
+
+use crate::explain::Reason;
+use crate::fingerprint::Fingerprint;
+use crate::{Dependency, Makefile, Target};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+/// Whether building `goals` would remake any target, for `-q`.
+pub fn out_of_date(makefile: &Makefile, goals: &[String]) -> Result<bool, crate::MakeError> {
+    let mut reasons = HashMap::new();
+    for goal in goals {
+        let target = makefile
+            .targets
+            .iter()
+            .find(|t| &t.name == goal)
+            .ok_or(crate::MakeError::NoSuchTarget)?;
+        if reason(makefile, target, &mut reasons).is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Print the tree of targets that building `goals` would run,
+/// followed by the targets grouped by the reason they would run
+/// and an estimate of the build time from previous builds.
+pub fn print(makefile: &Makefile, goals: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut by_reason: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    let mut reasons = HashMap::new();
+    let history = crate::history::read(&*makefile.fs)?;
+
+    for goal in goals {
+        let target = makefile
+            .targets
+            .iter()
+            .find(|t| &t.name == goal)
+            .ok_or(crate::MakeError::NoSuchTarget)?;
+        tree(makefile, target, "", "", &mut seen, &mut reasons);
+    }
+
+    for (target, reason) in &reasons {
+        if let Some(reason) = reason {
+            by_reason
+                .entry(reason.clone())
+                .or_default()
+                .push(target.to_string());
+        }
+    }
+    if by_reason.is_empty() {
+        println!();
+        println!("Everything is up to date.");
+        return Ok(());
+    }
+
+    println!();
+    println!("Targets by reason:");
+    let mut remade = 0;
+    for (reason, mut targets) in by_reason {
+        targets.sort();
+        remade += targets.len();
+        println!("  {}: {}", reason, targets.join(", "));
+    }
+
+    // Up to date targets and targets that never ran before
+    // are not part of the estimate.
+    let known: Vec<Duration> = reasons
+        .iter()
+        .filter(|(_, reason)| reason.is_some())
+        .filter_map(|(t, _)| Some(history.get(*t)?.duration))
+        .collect();
+    if !known.is_empty() {
+        println!();
+        println!(
+            "Estimated time: {:.1}s ({} of {} targets built before)",
+            known.iter().sum::<Duration>().as_secs_f64(),
+            known.len(),
+            remade
+        );
+    }
+    Ok(())
+}
+
+/// Print `target` and its dependencies as a tree. Targets that were
+/// already printed are only named again, not expanded.
+fn tree<'a>(
+    makefile: &'a Makefile,
+    target: &'a Target,
+    prefix: &str,
+    child_prefix: &str,
+    seen: &mut HashSet<&'a str>,
+    reasons: &mut HashMap<&'a str, Option<String>>,
+) {
+    if !seen.insert(&target.name) {
+        println!("{}{} (see above)", prefix, target.name);
+        return;
+    }
+    match reason(makefile, target, reasons) {
+        Some(reason) => println!("{}{} ({})", prefix, target.name, reason),
+        None => println!("{}{} (up to date)", prefix, target.name),
+    }
+
+    let children = children(makefile, target);
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        tree(
+            makefile,
+            child,
+            &format!("{}{}", child_prefix, branch),
+            &format!("{}{}", child_prefix, indent),
+            seen,
+            reasons,
+        );
+    }
+}
+
+/// The prerequisites of `target` that are targets themselves.
+fn children<'a>(makefile: &'a Makefile, target: &'a Target) -> Vec<&'a Target> {
+    target
+        .prerequisites()
+        .filter_map(|dep| match makefile.dependency(dep) {
+            (Dependency::Target(t), _, _) => Some(t),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Why `target` would be remade, or `None` if it is up to date,
+/// without downloading anything or running cargo. Results are
+/// remembered in `reasons`, since prerequisites decide for their dependents.
+fn reason<'a>(
+    makefile: &'a Makefile,
+    target: &'a Target,
+    reasons: &mut HashMap<&'a str, Option<String>>,
+) -> Option<String> {
+    if let Some(reason) = reasons.get(target.name.as_str()) {
+        return reason.clone();
+    }
+    // A remade prerequisite will be newer than the target, as is one
+    // without a file, unless it is order-only.
+    let mut remade = None;
+    for child in children(makefile, target) {
+        let normal = target
+            .dependencies
+            .iter()
+            .any(|dep| makefile.dependency(dep).1 == child.name);
+        let without_file = child.phony || !makefile.fs.exists(&child.file());
+        let child_reason = reason(makefile, child, reasons);
+        if (child_reason.is_some() || without_file) && normal && remade.is_none() {
+            remade = Some(format!("prerequisite '{}' will be remade", child.name));
+        }
+    }
+
+    let mut inputs = Vec::new();
+    let mut order_only = Vec::new();
+    let mut fetched = Vec::new();
+    let mut checkouts = Vec::new();
+    let deps = (target.dependencies.iter().map(|dep| (dep, false)))
+        .chain(target.order_only.iter().map(|dep| (dep, true)));
+    for (dep, is_order_only) in deps {
+        let path = match makefile.dependency(dep) {
+            (Dependency::Url(url), _, _) => {
+                let path = crate::fetch::cache_path(url).to_string_lossy().into_owned();
+                fetched.push(path.clone());
+                path
+            }
+            (Dependency::Git(url, _), _, _) => {
+                let path = crate::fetch::checkout_path(url)
+                    .to_string_lossy()
+                    .into_owned();
+                checkouts.push(path.clone());
+                path
+            }
+            (Dependency::File(f), _, _) => makefile.locate(f).unwrap_or_else(|| f.to_string()),
+            (_, name, _) => name.to_string(),
+        };
+        match is_order_only {
+            true => order_only.push(path),
+            false => inputs.push(path),
+        }
+    }
+    let idle = target.commands.is_empty() && target.cargo.is_none();
+    // With `--hash`, the fingerprint covers the recipe as it would run.
+    // If it can't be expanded, modification times decide.
+    let fingerprint = match makefile.options.hash {
+        true => {
+            let automatic = makefile.automatic(target, &inputs, &order_only, &fetched, &checkouts);
+            (makefile.recipe(target, &automatic).ok())
+                .map(|commands| Fingerprint::new(&*makefile.fs, &commands, &inputs))
+        }
+        false => None,
+    };
+    let reason = match makefile.out_of_date(target, &inputs, fingerprint.as_ref()) {
+        _ if idle => remade,
+        _ if target.phony => Some(Reason::Phony.to_string()),
+        Some(Reason::Missing) => Some(Reason::Missing.to_string()),
+        _ if target.cargo.is_some() => Some("cargo decides when building".to_string()),
+        Some(reason) => Some(reason.to_string()),
+        None if makefile.options.always_make => Some(Reason::AlwaysMake.to_string()),
+        None => remade,
+    };
+    reasons.insert(&target.name, reason.clone());
+    reason
+}
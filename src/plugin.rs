@@ -0,0 +1,246 @@
+//! Loading of native plugins with the `load` directive.
+//!
+//! A plugin is a shared object exporting `make_rs_plugin_setup`, which is called
+//! with a [PluginApi] to register functions, rules and execution hooks.
+//! The C declarations are in `plugin/make_rs_plugin.h`. Plugins are
+//! loaded with `dlopen` on Unix and `LoadLibrary` on Windows.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+/// Version of the plugin API passed to plugins.
+const API_VERSION: u32 = 1;
+
+/// `RTLD_NOW` for `dlopen` (the same on Linux and macOS).
+#[cfg(unix)]
+const RTLD_NOW: c_int = 0x2;
+
+#[cfg(unix)]
+extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *const c_char;
+}
+
+#[cfg(windows)]
+extern "system" {
+    fn LoadLibraryA(filename: *const c_char) -> *mut c_void;
+    fn GetProcAddress(module: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+/// Load the shared library `path`, or return null.
+///
+/// # Safety
+///
+/// Loading a library runs its initializers.
+#[cfg(unix)]
+unsafe fn open(path: &CStr) -> *mut c_void {
+    dlopen(path.as_ptr(), RTLD_NOW)
+}
+
+#[cfg(windows)]
+unsafe fn open(path: &CStr) -> *mut c_void {
+    LoadLibraryA(path.as_ptr())
+}
+
+/// Look up the symbol `name` in the library `handle`, or return null.
+///
+/// # Safety
+///
+/// `handle` must be a library returned by [open].
+#[cfg(unix)]
+unsafe fn symbol(handle: *mut c_void, name: &CStr) -> *mut c_void {
+    dlsym(handle, name.as_ptr())
+}
+
+#[cfg(windows)]
+unsafe fn symbol(handle: *mut c_void, name: &CStr) -> *mut c_void {
+    GetProcAddress(handle, name.as_ptr())
+}
+
+/// Why loading a library or looking up a symbol failed.
+#[cfg(unix)]
+fn last_error() -> String {
+    // SAFETY: dlerror returns either null or a valid C string.
+    unsafe {
+        let err = dlerror();
+        if err.is_null() {
+            "unknown error".to_string()
+        } else {
+            CStr::from_ptr(err).to_string_lossy().into_owned()
+        }
+    }
+}
+
+#[cfg(windows)]
+fn last_error() -> String {
+    std::io::Error::last_os_error().to_string()
+}
+
+/// Callback used by plugin functions to append to their expansion.
+pub type Write = extern "C" fn(output: *mut c_void, data: *const c_char, len: usize);
+
+/// A function implemented by a plugin. It receives the expanded arguments
+/// and writes its result through `write`.
+pub type Function =
+    extern "C" fn(argc: c_int, argv: *const *const c_char, output: *mut c_void, write: Write);
+
+/// A hook called with a [HookEvent] and the name of the target.
+pub type Hook = extern "C" fn(event: c_int, target: *const c_char);
+
+/// The events execution hooks are called for.
+#[derive(Debug, Clone, Copy)]
+pub enum HookEvent {
+    BeforeTarget = 0,
+    TargetSucceeded = 1,
+    TargetFailed = 2,
+}
+
+/// The table of callbacks handed to `make_rs_plugin_setup`.
+#[repr(C)]
+struct PluginApi {
+    version: u32,
+    context: *mut c_void,
+    add_function: extern "C" fn(context: *mut c_void, name: *const c_char, function: Function),
+    add_rule: extern "C" fn(
+        context: *mut c_void,
+        target: *const c_char,
+        prerequisites: *const c_char,
+        recipe: *const c_char,
+    ),
+    add_hook: extern "C" fn(context: *mut c_void, hook: Hook),
+}
+
+/// Everything a plugin registered during setup.
+/// Rules are (target, prerequisites, recipe lines).
+#[derive(Debug, Default)]
+pub struct Registry {
+    pub functions: Vec<(String, Function)>,
+    pub rules: Vec<(String, String, String)>,
+    pub hooks: Vec<Hook>,
+}
+
+/// Load the plugin at `path` and run its setup function.
+/// The library stays loaded until make-rs exits.
+pub fn load(path: &str) -> Result<Registry, Box<dyn std::error::Error>> {
+    // Libraries are only looked up in the search path for names without a slash.
+    let path = if path.contains(['/', std::path::MAIN_SEPARATOR]) {
+        path.to_string()
+    } else {
+        format!(".{}{}", std::path::MAIN_SEPARATOR, path)
+    };
+    let c_path = CString::new(path.clone())?;
+
+    // SAFETY: Loading a library runs its initializers, which is inherent
+    // to the `load` directive.
+    let handle = unsafe { open(&c_path) };
+    if handle.is_null() {
+        return Err(plugin_error(&path));
+    }
+    // SAFETY: `handle` is a valid library handle.
+    let setup = unsafe { symbol(handle, c"make_rs_plugin_setup") };
+    if setup.is_null() {
+        return Err(plugin_error(&path));
+    }
+    // SAFETY: plugins are required to export `make_rs_plugin_setup` with this signature.
+    let setup: extern "C" fn(*const PluginApi) -> c_int = unsafe { std::mem::transmute(setup) };
+
+    let mut registry = Registry::default();
+    let api = PluginApi {
+        version: API_VERSION,
+        context: &mut registry as *mut Registry as *mut c_void,
+        add_function,
+        add_rule,
+        add_hook,
+    };
+    if setup(&api) != 0 {
+        crate::diagnostic::error(format!("setup of plugin {} failed", path));
+        return Err(Box::new(crate::MakeError::PluginError));
+    }
+
+    Ok(registry)
+}
+
+/// Call a plugin function with `args` and collect its output.
+pub fn call(function: Function, args: &[String]) -> Result<String, Box<dyn std::error::Error>> {
+    let args = args
+        .iter()
+        .map(|arg| CString::new(arg.as_str()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let argv: Vec<*const c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+
+    let mut output: Vec<u8> = Vec::new();
+    function(
+        argv.len() as c_int,
+        argv.as_ptr(),
+        &mut output as *mut Vec<u8> as *mut c_void,
+        write,
+    );
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Call all `hooks` for `event` on `target`.
+pub fn run_hooks(hooks: &[Hook], event: HookEvent, target: &str) {
+    let Ok(target) = CString::new(target) else {
+        return;
+    };
+    for hook in hooks {
+        hook(event as c_int, target.as_ptr());
+    }
+}
+
+/// Report why a plugin failed to load.
+fn plugin_error(path: &str) -> Box<dyn std::error::Error> {
+    let message = last_error();
+    crate::diagnostic::error(format!("cannot load plugin {}: {}", path, message));
+    Box::new(crate::MakeError::PluginError)
+}
+
+/// Convert a C string from a plugin, treating null as empty.
+fn string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    // SAFETY: plugins pass valid, NUL-terminated strings.
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Get the registry behind the context pointer of the API table.
+fn registry<'a>(context: *mut c_void) -> &'a mut Registry {
+    // SAFETY: `context` is the registry given to setup, which outlives the call.
+    unsafe { &mut *(context as *mut Registry) }
+}
+
+extern "C" fn add_function(context: *mut c_void, name: *const c_char, function: Function) {
+    registry(context).functions.push((string(name), function));
+}
+
+extern "C" fn add_rule(
+    context: *mut c_void,
+    target: *const c_char,
+    prerequisites: *const c_char,
+    recipe: *const c_char,
+) {
+    registry(context)
+        .rules
+        .push((string(target), string(prerequisites), string(recipe)));
+}
+
+extern "C" fn add_hook(context: *mut c_void, hook: Hook) {
+    registry(context).hooks.push(hook);
+}
+
+extern "C" fn write(output: *mut c_void, data: *const c_char, len: usize) {
+    // A plugin may pass a null pointer for nothing to write, which
+    // `from_raw_parts` doesn't allow.
+    if output.is_null() || data.is_null() || len == 0 {
+        return;
+    }
+    // SAFETY: `output` is the buffer passed to the function being called
+    // and `data` points to `len` readable bytes.
+    unsafe {
+        let output = &mut *(output as *mut Vec<u8>);
+        output.extend_from_slice(std::slice::from_raw_parts(data as *const u8, len));
+    }
+}
@@ -0,0 +1,239 @@
+//! Starting and stopping the processes of recipe commands, which works
+//! differently on Unix and Windows.
+//!
+//! On Unix, every command runs in a process group of its own, so that
+//! the processes it started are stopped with it. While a command runs,
+//! its process group gets the terminal make-rs was started from, so it
+//! can read from it and Ctrl-C reaches it directly. Windows has neither
+//! process groups nor signals, so commands are simply killed there.
+
+#[cfg(unix)]
+pub use unix::{exit_signal, spawn, terminate, try_wait, Terminal};
+#[cfg(windows)]
+pub use windows::{exit_signal, spawn, terminate, try_wait, Terminal};
+
+use std::process::ExitStatus;
+use std::time::Duration;
+
+/// How a command exited.
+#[derive(Debug, Clone, Copy)]
+pub struct Exit {
+    pub status: ExitStatus,
+    /// The CPU time used by the command and the processes it waited for,
+    /// if it is known.
+    pub cpu: Option<Duration>,
+}
+
+/// `SIGINT` and `SIGTERM`, which are the same on all platforms make-rs
+/// runs on, including the C runtime of Windows.
+pub const SIGINT: std::ffi::c_int = 2;
+pub const SIGTERM: std::ffi::c_int = 15;
+
+#[cfg(unix)]
+mod unix {
+    use super::Exit;
+    use std::ffi::{c_int, c_long, c_void};
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+    use std::process::{Child, Command, ExitStatus};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// How long a terminated command may take to exit before it is killed.
+    const TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// `SIGKILL` and `SIGTTOU`, which are the same on all Unix platforms
+    /// make-rs runs on.
+    const SIGKILL: c_int = 9;
+    const SIGTTOU: c_int = 22;
+
+    /// `WNOHANG` of `wait4`, which returns right away if the process is running.
+    const WNOHANG: c_int = 1;
+
+    /// `SIGCONT` and the `how` of `pthread_sigmask`, which differ between
+    /// Linux and the BSDs.
+    #[cfg(target_os = "linux")]
+    const SIGCONT: c_int = 18;
+    #[cfg(not(target_os = "linux"))]
+    const SIGCONT: c_int = 19;
+    #[cfg(target_os = "linux")]
+    const SIG_BLOCK: c_int = 0;
+    #[cfg(not(target_os = "linux"))]
+    const SIG_BLOCK: c_int = 1;
+    #[cfg(target_os = "linux")]
+    const SIG_SETMASK: c_int = 2;
+    #[cfg(not(target_os = "linux"))]
+    const SIG_SETMASK: c_int = 3;
+
+    extern "C" {
+        fn kill(pid: c_int, signal: c_int) -> c_int;
+        fn isatty(fd: c_int) -> c_int;
+        fn getpgrp() -> c_int;
+        fn tcgetpgrp(fd: c_int) -> c_int;
+        fn tcsetpgrp(fd: c_int, group: c_int) -> c_int;
+        fn sigemptyset(set: *mut c_void) -> c_int;
+        fn sigaddset(set: *mut c_void, signal: c_int) -> c_int;
+        fn pthread_sigmask(how: c_int, set: *const c_void, old: *mut c_void) -> c_int;
+        fn wait4(pid: c_int, status: *mut c_int, options: c_int, usage: *mut Usage) -> c_int;
+    }
+
+    /// A `struct timeval`.
+    #[repr(C)]
+    #[derive(Default)]
+    struct Timeval {
+        seconds: c_long,
+        #[cfg(target_os = "macos")]
+        microseconds: i32,
+        #[cfg(not(target_os = "macos"))]
+        microseconds: c_long,
+    }
+
+    impl Timeval {
+        fn duration(&self) -> Duration {
+            Duration::from_secs(self.seconds as u64)
+                + Duration::from_micros(self.microseconds as u64)
+        }
+    }
+
+    /// A `struct rusage`, of which only the CPU times are used.
+    #[repr(C)]
+    #[derive(Default)]
+    struct Usage {
+        user: Timeval,
+        system: Timeval,
+        /// The other fields, with room to spare.
+        rest: [c_long; 16],
+    }
+
+    /// Start `command` in a process group of its own.
+    pub fn spawn(command: &mut Command) -> std::io::Result<Child> {
+        command.process_group(0).spawn()
+    }
+
+    /// The exit of `child` if it exited, like [Child::try_wait], with the
+    /// CPU time it used.
+    pub fn try_wait(child: &mut Child) -> std::io::Result<Option<Exit>> {
+        let mut status = 0;
+        let mut usage = Usage::default();
+        // SAFETY: `usage` is at least as large as a `struct rusage`.
+        match unsafe { wait4(child.id() as c_int, &mut status, WNOHANG, &mut usage) } {
+            -1 => Err(std::io::Error::last_os_error()),
+            0 => Ok(None),
+            _ => Ok(Some(Exit {
+                status: ExitStatus::from_raw(status),
+                cpu: Some(usage.user.duration() + usage.system.duration()),
+            })),
+        }
+    }
+
+    /// The signal that killed a process with `status`, if any.
+    pub fn exit_signal(status: &ExitStatus) -> Option<i32> {
+        status.signal()
+    }
+
+    /// Stop the process group of `child` with `signal`, and kill it if it
+    /// doesn't exit in time.
+    pub fn terminate(child: &mut Child, signal: c_int) {
+        let group = -(child.id() as c_int);
+        // SAFETY: `kill` only sends a signal.
+        unsafe { kill(group, signal) };
+        let deadline = Instant::now() + TERMINATE_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // SAFETY: as above.
+        unsafe { kill(group, SIGKILL) };
+        let _ = child.wait();
+    }
+
+    /// Whether a command has the terminal. Only one can have it at a time.
+    static TERMINAL: AtomicBool = AtomicBool::new(false);
+
+    /// The terminal on stdin, lent to the process group of a command.
+    /// It is given back to make-rs when this is dropped.
+    pub struct Terminal;
+
+    impl Terminal {
+        /// Make the process group of `child` the foreground process group
+        /// of the terminal, if make-rs is in the foreground and no other
+        /// command has it.
+        pub fn give(child: &Child) -> Option<Self> {
+            let group = child.id() as c_int;
+            // SAFETY: these only look up process groups.
+            let foreground = unsafe { isatty(0) == 1 && tcgetpgrp(0) == getpgrp() };
+            if !foreground || TERMINAL.swap(true, Ordering::SeqCst) {
+                return None;
+            }
+            set_foreground(group);
+            // The command is stopped if it read from the terminal before it had it.
+            // SAFETY: `kill` only sends a signal.
+            unsafe { kill(-group, SIGCONT) };
+            Some(Self)
+        }
+    }
+
+    impl Drop for Terminal {
+        fn drop(&mut self) {
+            // SAFETY: `getpgrp` can't fail.
+            set_foreground(unsafe { getpgrp() });
+            TERMINAL.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Make `group` the foreground process group of the terminal on stdin.
+    /// make-rs may be in the background while it takes the terminal back,
+    /// so `SIGTTOU` is blocked meanwhile instead of stopping it.
+    fn set_foreground(group: c_int) {
+        // Large enough for a `sigset_t` on all platforms make-rs runs on.
+        let mut set = [0u64; 16];
+        let mut old = [0u64; 16];
+        // SAFETY: the sets are large enough and only this thread's mask changes.
+        unsafe {
+            sigemptyset(set.as_mut_ptr().cast());
+            sigaddset(set.as_mut_ptr().cast(), SIGTTOU);
+            pthread_sigmask(SIG_BLOCK, set.as_ptr().cast(), old.as_mut_ptr().cast());
+            tcsetpgrp(0, group);
+            pthread_sigmask(SIG_SETMASK, old.as_ptr().cast(), std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::Exit;
+    use std::ffi::c_int;
+    use std::process::{Child, Command, ExitStatus};
+
+    /// Start `command`.
+    pub fn spawn(command: &mut Command) -> std::io::Result<Child> {
+        command.spawn()
+    }
+
+    /// The exit of `child` if it exited. Its CPU time is not known.
+    pub fn try_wait(child: &mut Child) -> std::io::Result<Option<Exit>> {
+        let status = child.try_wait()?;
+        Ok(status.map(|status| Exit { status, cpu: None }))
+    }
+
+    /// Processes on Windows are not killed by signals.
+    pub fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+        None
+    }
+
+    /// Kill `child`. There are no signals to ask it to stop first.
+    pub fn terminate(child: &mut Child, _signal: c_int) {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    /// Commands share the console with make-rs, so there is nothing to lend.
+    pub struct Terminal;
+
+    impl Terminal {
+        pub fn give(_child: &Child) -> Option<Self> {
+            None
+        }
+    }
+}
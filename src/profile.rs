@@ -0,0 +1,86 @@
+//! `--profile`: how long each recipe took, as a summary at the end of the
+//! build and optionally as a [Chrome trace](https://ui.perfetto.dev) of
+//! the build timeline.
+
+use crate::json;
+use std::time::{Duration, SystemTime};
+
+/// When a target's recipe ran and how long it took.
+#[derive(Debug, Clone)]
+pub struct Timing {
+    pub target: String,
+    pub started: SystemTime,
+    pub duration: Duration,
+    /// The CPU time the recipe used, if it is known.
+    pub cpu: Option<Duration>,
+}
+
+/// The recipes that ran, slowest first, with their share of the time
+/// all recipes took. `elapsed` is how long the whole build took.
+pub fn summary(timings: &[Timing], elapsed: Duration) -> String {
+    let mut timings = timings.to_vec();
+    timings.sort_by_key(|t| std::cmp::Reverse(t.duration));
+    let total: Duration = timings.iter().map(|t| t.duration).sum();
+    let recipes = match timings.len() {
+        1 => "1 recipe".to_string(),
+        n => format!("{} recipes", n),
+    };
+    let mut summary = format!(
+        "make-rs: {} took {:.2}s, the build {:.2}s\n",
+        recipes,
+        total.as_secs_f64(),
+        elapsed.as_secs_f64()
+    );
+    for timing in &timings {
+        let share = match total.is_zero() {
+            true => 0.0,
+            false => timing.duration.as_secs_f64() / total.as_secs_f64() * 100.0,
+        };
+        summary.push_str(&format!(
+            "  {:>8.2}s {:>5.1}%  {}\n",
+            timing.duration.as_secs_f64(),
+            share,
+            timing.target
+        ));
+    }
+    summary
+}
+
+/// The recipes as Chrome trace events. Recipes that ran at the same
+/// time are put on different threads, so they show up as parallel lanes.
+pub fn trace(timings: &[Timing]) -> String {
+    let mut timings = timings.to_vec();
+    timings.sort_by_key(|t| t.started);
+    let Some(start) = timings.first().map(|t| t.started) else {
+        return "{\"traceEvents\":[]}\n".to_string();
+    };
+
+    // Each lane is free again once its last recipe ended.
+    let mut lanes: Vec<SystemTime> = Vec::new();
+    let mut events = Vec::new();
+    for timing in &timings {
+        let finished = timing.started + timing.duration;
+        let lane = match lanes.iter().position(|end| *end <= timing.started) {
+            Some(lane) => {
+                lanes[lane] = finished;
+                lane
+            }
+            None => {
+                lanes.push(finished);
+                lanes.len() - 1
+            }
+        };
+        let offset = timing.started.duration_since(start).unwrap_or_default();
+        events.push(format!(
+            "{{\"name\":{},\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}}",
+            json::string(&timing.target),
+            offset.as_micros(),
+            timing.duration.as_micros(),
+            lane + 1
+        ));
+    }
+    format!(
+        "{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n",
+        events.join(",\n")
+    )
+}
@@ -0,0 +1,85 @@
+//! Provenance attestations for built artifacts, following the
+//! in-toto statement and SLSA provenance formats.
+
+use crate::json;
+use crate::vfs::Fs;
+
+/// Directory that attestations are written to.
+const PROVENANCE_DIR: &str = ".make-rs/provenance";
+
+/// Environment variables with these words in their name
+/// are left out of attestations, as they likely hold secrets.
+const SECRET_WORDS: [&str; 5] = ["TOKEN", "SECRET", "PASSWORD", "KEY", "CREDENTIAL"];
+
+/// How an artifact was produced.
+#[derive(Debug)]
+pub struct Provenance<'a> {
+    pub target: &'a str,
+    pub inputs: &'a [String],
+    pub commands: &'a [String],
+    pub started: std::time::SystemTime,
+    pub finished: std::time::SystemTime,
+}
+
+impl Provenance<'_> {
+    /// Write the attestation for the target to `.make-rs/provenance/<target>.intoto.json`
+    /// in `fs`. Targets that do not produce a file (e.g. `clean`) have nothing to attest.
+    pub fn write(&self, fs: &dyn Fs) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(subject) = digest_entry(fs, self.target) else {
+            return Ok(());
+        };
+        let dependencies: Vec<String> = self
+            .inputs
+            .iter()
+            .filter_map(|input| digest_entry(fs, input))
+            .collect();
+        let commands: Vec<String> = self.commands.iter().map(|c| json::string(c)).collect();
+        let environment: Vec<String> = std::env::vars()
+            .filter(|(name, _)| {
+                let name = name.to_uppercase();
+                !SECRET_WORDS.iter().any(|word| name.contains(word))
+            })
+            .map(|(name, value)| format!("{}:{}", json::string(&name), json::string(&value)))
+            .collect();
+
+        let statement = format!(
+            concat!(
+                "{{\"_type\":\"https://in-toto.io/Statement/v1\",",
+                "\"subject\":[{}],",
+                "\"predicateType\":\"https://slsa.dev/provenance/v1\",",
+                "\"predicate\":{{\"buildDefinition\":{{",
+                "\"buildType\":\"https://github.com/ctiedt/make-rs\",",
+                "\"externalParameters\":{{\"target\":{},\"commands\":[{}]}},",
+                "\"internalParameters\":{{\"environment\":{{{}}}}},",
+                "\"resolvedDependencies\":[{}]}},",
+                "\"runDetails\":{{\"builder\":{{\"id\":{}}},",
+                "\"metadata\":{{\"startedOn\":{},\"finishedOn\":{}}}}}}}}}\n"
+            ),
+            subject,
+            json::string(self.target),
+            commands.join(","),
+            environment.join(","),
+            dependencies.join(","),
+            json::string(concat!("make-rs ", env!("CARGO_PKG_VERSION"))),
+            json::string(&crate::functions::iso8601(self.started)),
+            json::string(&crate::functions::iso8601(self.finished)),
+        );
+
+        let file = format!("{}.intoto.json", self.target.replace('/', "_"));
+        fs.write(
+            &format!("{}/{}", PROVENANCE_DIR, file),
+            statement.as_bytes(),
+        )?;
+        Ok(())
+    }
+}
+
+/// A resource descriptor with the sha256 of `path`, if it is a readable file.
+fn digest_entry(fs: &dyn Fs, path: &str) -> Option<String> {
+    let data = fs.read(path).ok()?;
+    Some(format!(
+        "{{\"name\":{},\"digest\":{{\"sha256\":{}}}}}",
+        json::string(path),
+        json::string(&crate::functions::sha256_hex(&data))
+    ))
+}
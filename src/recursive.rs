@@ -0,0 +1,151 @@
+//! Recursive invocations like `$(MAKE) -C subdir`.
+//!
+//! Like in GNU make, `$(MAKE)` is the running make-rs, `MAKEFLAGS` passes
+//! its options and command line variables on to the make-rs it runs, and
+//! `MAKELEVEL` tells how deeply it is nested (0 for the outermost one).
+
+use crate::expand::{Expander, Flavor, Origin};
+use crate::Options;
+
+/// The single-letter flags that are passed on in `MAKEFLAGS`.
+const FLAGS: [char; 5] = ['B', 'k', 'r', 's', 't'];
+
+/// Define `MAKE`, `MAKEFLAGS` and `MAKELEVEL` in `expander` for the
+/// given options. `MAKEFLAGS` is exported, the environment of recipes
+/// gets `MAKELEVEL` from [child_level].
+pub fn define_variables(
+    expander: &mut Expander,
+    options: &Options,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let make = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "make-rs".to_string());
+    expander.assign("MAKE", &escape(&make), Flavor::Simple, Origin::Default)?;
+    define_flags(expander, options)?;
+    // It replaces the value make-rs was started with, like `MAKEFLAGS`.
+    let level = level().to_string();
+    expander.assign("MAKELEVEL", &level, Flavor::Simple, Origin::Environment)?;
+    Ok(())
+}
+
+/// Define and export `MAKEFLAGS` for `options` in `expander`.
+pub fn define_flags(
+    expander: &mut Expander,
+    options: &Options,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let flags = escape_dollars(&encode(options));
+    expander.assign("MAKEFLAGS", &flags, Flavor::Simple, Origin::Environment)?;
+    expander.export("MAKEFLAGS");
+    Ok(())
+}
+
+/// How deeply the running make-rs is nested in other invocations.
+pub fn level() -> u32 {
+    std::env::var("MAKELEVEL")
+        .ok()
+        .and_then(|level| level.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// The `MAKELEVEL` of the make-rs invocations recipes run.
+pub fn child_level() -> String {
+    (level() + 1).to_string()
+}
+
+/// The command line arguments given by the `MAKEFLAGS` of the environment,
+/// which come before the real ones. Flags make-rs doesn't know (like those
+/// of GNU make) are left out.
+pub fn inherited() -> Vec<String> {
+    std::env::var("MAKEFLAGS")
+        .map(|flags| decode(&flags))
+        .unwrap_or_default()
+}
+
+/// `MAKEFLAGS` for `options`: the single-letter flags in one word,
+/// then `-j` and the jobserver, then the command line variables.
+fn encode(options: &Options) -> String {
+    let set = [
+        options.always_make,
+        options.keep_going,
+        options.no_builtin_rules,
+        options.silent,
+        options.touch,
+    ];
+    let letters: String = FLAGS
+        .iter()
+        .zip(set)
+        .filter(|(_, set)| *set)
+        .map(|(flag, _)| flag)
+        .collect();
+    let mut words = Vec::new();
+    if !letters.is_empty() {
+        words.push(letters);
+    }
+    if let Some(jobs) = &options.jobs {
+        words.push(format!("-j{}", jobs));
+    }
+    if let Some(auth) = &options.jobserver {
+        words.push(format!("--jobserver-auth={}", auth));
+    }
+    if !options.variables.is_empty() {
+        words.push("--".to_string());
+        words.extend(options.variables.iter().map(|v| v.replace(' ', "\\ ")));
+    }
+    words.join(" ")
+}
+
+/// The arguments for `MAKEFLAGS`. Like in GNU make, the first word may
+/// hold single-letter flags without a `-`, and variables follow `--`.
+fn decode(flags: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut variables = false;
+    for (i, word) in split(flags).into_iter().enumerate() {
+        if variables {
+            args.push(word);
+        } else if word == "--" {
+            variables = true;
+        } else if word.starts_with("-j") || word.starts_with("--jobserver-") {
+            args.push(word);
+        } else if let Some(letters) = word.strip_prefix('-').or((i == 0).then_some(&word[..])) {
+            // Long options and single letters with arguments are skipped.
+            if !letters.starts_with('-') && !letters.contains('=') {
+                let known = letters.chars().filter(|flag| FLAGS.contains(flag));
+                args.extend(known.map(|flag| format!("-{}", flag)));
+            }
+        }
+    }
+    args
+}
+
+/// Split `MAKEFLAGS` into words at whitespace that isn't escaped with a backslash.
+fn split(flags: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = flags.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => word.extend(chars.next()),
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Quote the path of the binary for the shell if it has spaces, and
+/// keep `$` from being expanded.
+fn escape(path: &str) -> String {
+    escape_dollars(&crate::shell_quote(path))
+}
+
+/// Keep `$` in a value from being expanded.
+fn escape_dollars(value: &str) -> String {
+    value.replace('$', "$$")
+}
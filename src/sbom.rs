@@ -0,0 +1,85 @@
+//! Software bills of materials generated from the dependency graph.
+
+use crate::{json, Dependency, MakeError, Makefile};
+
+/// A CycloneDX (1.5) document listing the source inputs and produced
+/// artifacts of `goals` with their hashes, and how they depend on each other.
+pub fn cyclonedx(makefile: &Makefile, goals: &[String]) -> Result<String, MakeError> {
+    let mut targets = Vec::new();
+    for goal in goals {
+        for target in makefile.resolve(goal)? {
+            if !targets
+                .iter()
+                .any(|t: &&crate::Target| t.name == target.name)
+            {
+                targets.push(target);
+            }
+        }
+    }
+
+    let mut components = Vec::new();
+    let mut sources: Vec<String> = Vec::new();
+    let mut dependencies = Vec::new();
+    for target in &targets {
+        let mut depends_on = Vec::new();
+        for dep in &target.dependencies {
+            let (dep, name, _) = makefile.dependency(dep);
+            let source = match dep {
+                Dependency::Target(t) => {
+                    depends_on.push(t.name.clone());
+                    continue;
+                }
+                Dependency::File(file) => file.to_string(),
+                Dependency::Url(url) => {
+                    crate::fetch::cache_path(url).to_string_lossy().into_owned()
+                }
+                Dependency::Git(_, _) => name.to_string(),
+            };
+            depends_on.push(source.clone());
+            if !sources.contains(&source) {
+                components.push(component(&source, "source"));
+                sources.push(source);
+            }
+        }
+        components.push(component(&target.name, "artifact"));
+        dependencies.push(format!(
+            "{{\"ref\":{},\"dependsOn\":{}}}",
+            json::string(&target.name),
+            json::string_array(&depends_on)
+        ));
+    }
+
+    Ok(format!(
+        concat!(
+            "{{\"bomFormat\":\"CycloneDX\",\"specVersion\":\"1.5\",\"version\":1,",
+            "\"metadata\":{{\"timestamp\":{},\"tools\":{{\"components\":[",
+            "{{\"type\":\"application\",\"name\":\"make-rs\",\"version\":{}}}]}},",
+            "\"component\":{{\"type\":\"application\",\"name\":{}}}}},",
+            "\"components\":[{}],\"dependencies\":[{}]}}\n"
+        ),
+        json::string(&crate::functions::iso8601(std::time::SystemTime::now())),
+        json::string(env!("CARGO_PKG_VERSION")),
+        json::string(&goals.join(" ")),
+        components.join(","),
+        dependencies.join(",")
+    ))
+}
+
+/// A file component with the given role (`source` or `artifact`),
+/// hashed if the file exists.
+fn component(name: &str, role: &str) -> String {
+    let hashes = match std::fs::read(name) {
+        Ok(data) => format!(
+            ",\"hashes\":[{{\"alg\":\"SHA-256\",\"content\":{}}}]",
+            json::string(&crate::functions::sha256_hex(&data))
+        ),
+        Err(_) => String::new(),
+    };
+    format!(
+        "{{\"type\":\"file\",\"bom-ref\":{},\"name\":{}{},\"properties\":[{{\"name\":\"make-rs:role\",\"value\":{}}}]}}",
+        json::string(name),
+        json::string(name),
+        hashes,
+        json::string(role)
+    )
+}
@@ -0,0 +1,281 @@
+//! Parallel builds with `-j N`.
+//!
+//! The targets needed for the goals are collected into a graph first.
+//! Every target whose prerequisites are made is ready, and up to N
+//! ready targets are remade at the same time on their own threads.
+//! Ready targets that took longest in previous builds start first,
+//! so long recipes don't end up running alone at the end of the build.
+//!
+//! Recipes that kept several cores busy in previous builds (like a
+//! compiler or linker running threads of its own) take as many of the
+//! N slots, so they don't share the CPUs with N-1 other recipes. A recipe
+//! that needs more slots than are free waits until enough are, unless
+//! nothing else is running.
+
+use crate::debug::{self, Category};
+use crate::{Dependency, MakeError, Makefile, Target};
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+/// A target in the graph, with the targets among its prerequisites.
+struct Node<'a> {
+    target: &'a Target,
+    /// Indices of the nodes this one depends on.
+    prerequisites: Vec<usize>,
+    /// Indices of the nodes depending on this one.
+    dependents: Vec<usize>,
+    /// Excluded targets are not remade.
+    excluded: bool,
+}
+
+/// Make `goals` with up to `jobs` recipes running at once.
+/// Returns whether any recipe ran for each goal.
+pub fn build(
+    makefile: &Makefile,
+    goals: &[String],
+    jobs: usize,
+) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    let (nodes, roots) = graph(makefile, goals)?;
+    let history = crate::history::read(&*makefile.fs)?;
+    let entry = |node: &Node| history.get(&node.target.name).copied().unwrap_or_default();
+    let expected = |node: &Node| entry(node).duration;
+    let weight = |node: &Node| entry(node).cores().min(jobs);
+
+    let mut waiting: Vec<usize> = nodes.iter().map(|n| n.prerequisites.len()).collect();
+    let mut made: Vec<Option<bool>> = vec![None; nodes.len()];
+    let mut ready: Vec<usize> = (0..nodes.len()).filter(|&i| waiting[i] == 0).collect();
+    let mut running = 0;
+    // The slots taken by the running recipes.
+    let mut busy = 0;
+    let mut weights = vec![0; nodes.len()];
+    let mut error = None;
+    let mut blocked = vec![false; nodes.len()];
+
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        loop {
+            // After a failure, running recipes are finished but no new
+            // ones start, unless `-k` asks to keep going.
+            let stopped = error.is_some() && !makefile.keep_going();
+            while !stopped && busy < jobs && !ready.is_empty() {
+                // The longest target goes first, ties in the order of the Makefile.
+                let next = (0..ready.len())
+                    .rev()
+                    .max_by_key(|&i| expected(&nodes[ready[i]]))
+                    .unwrap();
+                let node = &nodes[ready[next]];
+                if !node.excluded && running > 0 && busy + weight(node) > jobs {
+                    break;
+                }
+                let index = ready.remove(next);
+                if node.excluded {
+                    finish(&nodes, index, false, &mut made, &mut waiting, &mut ready);
+                    continue;
+                }
+                let prerequisites: HashMap<&str, bool> = node
+                    .prerequisites
+                    .iter()
+                    .map(|&i| (nodes[i].target.name.as_str(), made[i].unwrap()))
+                    .collect();
+                weights[index] = weight(node);
+                debug::log(Category::Jobs, || {
+                    format!(
+                        "starting '{}' with weight {} ({} of {} slots taken)",
+                        node.target.name,
+                        weights[index],
+                        busy + weights[index],
+                        jobs
+                    )
+                });
+                let sender = sender.clone();
+                let target = node.target;
+                scope.spawn(move || {
+                    let result = makefile.remake(target, &prerequisites).map_err(sendable);
+                    let _ = sender.send((index, result));
+                });
+                running += 1;
+                busy += weights[index];
+            }
+            if running == 0 {
+                break;
+            }
+            let (index, result) = receiver.recv().expect("a job is running");
+            running -= 1;
+            busy -= weights[index];
+            debug::log(Category::Jobs, || {
+                let status = if result.is_ok() { "finished" } else { "failed" };
+                format!(
+                    "'{}' {}, {} ready",
+                    nodes[index].target.name,
+                    status,
+                    ready.len()
+                )
+            });
+            // Hooks that run after the build don't make these targets again.
+            makefile.record_made(&nodes[index].target.name, result.as_ref().ok().copied());
+            match result {
+                Ok(ran) => finish(&nodes, index, ran, &mut made, &mut waiting, &mut ready),
+                Err(err) => {
+                    error.get_or_insert(err);
+                    if makefile.keep_going() {
+                        block(&nodes, index, &mut blocked);
+                    }
+                }
+            }
+        }
+    });
+
+    if let Some(err) = error {
+        return Err(err as Box<dyn std::error::Error>);
+    }
+    Ok(roots.iter().map(|&i| made[i].unwrap_or(false)).collect())
+}
+
+/// Make `err` sendable to the scheduling thread. Errors other than
+/// [MakeError] are passed on as their message.
+fn sendable(err: Box<dyn std::error::Error>) -> Box<dyn std::error::Error + Send> {
+    match err.downcast::<MakeError>() {
+        Ok(err) => err,
+        Err(err) => Box::<dyn std::error::Error + Send + Sync>::from(err.to_string()),
+    }
+}
+
+/// Record the result of the node `index` and mark the dependents
+/// that have all their prerequisites made as ready.
+fn finish(
+    nodes: &[Node],
+    index: usize,
+    ran: bool,
+    made: &mut [Option<bool>],
+    waiting: &mut [usize],
+    ready: &mut Vec<usize>,
+) {
+    made[index] = Some(ran);
+    for &dependent in &nodes[index].dependents {
+        waiting[dependent] -= 1;
+        if waiting[dependent] == 0 {
+            ready.push(dependent);
+        }
+    }
+}
+
+/// Report the targets depending on the failed node `index`,
+/// which can't be remade anymore.
+fn block(nodes: &[Node], index: usize, blocked: &mut [bool]) {
+    for &dependent in &nodes[index].dependents {
+        if !blocked[dependent] {
+            blocked[dependent] = true;
+            crate::diagnostic::error(format!(
+                "target '{}' not remade because of errors",
+                nodes[dependent].target.name
+            ));
+            block(nodes, dependent, blocked);
+        }
+    }
+}
+
+/// Collect the targets needed for `goals` into nodes, returning
+/// them together with the indices of the goals.
+fn graph<'a>(
+    makefile: &'a Makefile,
+    goals: &[String],
+) -> Result<(Vec<Node<'a>>, Vec<usize>), Box<dyn std::error::Error>> {
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    let mut roots = Vec::new();
+    let mut pending: Vec<(&str, Option<usize>)> = goals
+        .iter()
+        .rev()
+        .map(|goal| (goal.as_str(), None))
+        .collect();
+
+    while let Some((name, dependent)) = pending.pop() {
+        let index = match indices.get(name) {
+            Some(&index) => index,
+            None => {
+                let index = nodes.len();
+                let (target, excluded) = match makefile.prepare(name)? {
+                    Some(target) => (target, false),
+                    None => (
+                        makefile.targets.iter().find(|t| t.name == name).unwrap(),
+                        true,
+                    ),
+                };
+                nodes.push(Node {
+                    target,
+                    prerequisites: Vec::new(),
+                    dependents: Vec::new(),
+                    excluded,
+                });
+                indices.insert(&target.name, index);
+                // The targets of a grouped rule are made by one run of its recipe.
+                for member in &target.group {
+                    indices.insert(member, index);
+                }
+                if !excluded {
+                    for dep in target.prerequisites().rev() {
+                        if let (Dependency::Target(t), _, _) = makefile.dependency(dep) {
+                            pending.push((&t.name, Some(index)));
+                        }
+                    }
+                }
+                index
+            }
+        };
+        match dependent {
+            Some(dependent) if !nodes[dependent].prerequisites.contains(&index) => {
+                nodes[dependent].prerequisites.push(index);
+                nodes[index].dependents.push(dependent);
+            }
+            Some(_) => {}
+            None => roots.push(index),
+        }
+    }
+
+    // `.WAIT` and `.NOTPARALLEL` order prerequisites without making them
+    // depend on each other.
+    for index in 0..nodes.len() {
+        let target = nodes[index].target;
+        if target.waits.is_empty() && !target.not_parallel {
+            continue;
+        }
+        let mut before: Vec<usize> = Vec::new();
+        let mut current: Vec<usize> = Vec::new();
+        for dep in target.prerequisites() {
+            let (Dependency::Target(t), _, _) = makefile.dependency(dep) else {
+                continue;
+            };
+            let Some(&prerequisite) = indices.get(t.name.as_str()) else {
+                continue;
+            };
+            if target.not_parallel || target.waits.contains(dep) {
+                before.append(&mut current);
+            }
+            for &earlier in &before {
+                order(&mut nodes, earlier, prerequisite);
+            }
+            current.push(prerequisite);
+        }
+    }
+    Ok((nodes, roots))
+}
+
+/// Make the node `after` wait for the node `before`, unless `before`
+/// needs `after` itself, which would never let either start.
+fn order(nodes: &mut [Node], before: usize, after: usize) {
+    if nodes[after].prerequisites.contains(&before) {
+        return;
+    }
+    let mut visited = vec![false; nodes.len()];
+    let mut pending = vec![before];
+    while let Some(node) = pending.pop() {
+        if node == after {
+            return;
+        }
+        if !std::mem::replace(&mut visited[node], true) {
+            pending.extend(&nodes[node].prerequisites);
+        }
+    }
+    nodes[after].prerequisites.push(before);
+    nodes[before].dependents.push(after);
+}
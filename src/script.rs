@@ -0,0 +1,59 @@
+//! `script ... endscript` blocks for logic that is awkward in make syntax.
+//! A block is run by an interpreter (`sh` unless named after `script`)
+//! when the parser reaches it, and its output is parsed in place of the
+//! block, so scripts can compute variables and generate rules. Blocks in
+//! branches of conditionals that are not taken don't run.
+
+use crate::source::Line;
+
+/// The interpreter of the script block opened by `line`: `sh` for a bare
+/// `script`, otherwise the program named after it. Lines with `=` or `:`
+/// are assignments or rules, like `script = deploy.sh`.
+pub fn interpreter(line: &str) -> Option<&str> {
+    if line.starts_with('\t') || line.contains(['=', ':']) {
+        return None;
+    }
+    match line.trim_end().strip_prefix("script")? {
+        "" => Some("sh"),
+        interpreter if interpreter.starts_with(char::is_whitespace) => Some(interpreter.trim()),
+        _ => None,
+    }
+}
+
+/// Run the script block `line`, which source::read made of the `script`
+/// line and the code after the first newline. Returns its output.
+pub fn run(line: &Line) -> Result<String, Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    let (header, code) = line.text.split_once('\n').unwrap_or((&line.text, ""));
+    let interpreter = interpreter(header).unwrap_or("sh");
+    // Errors point at the `script` line.
+    let at = Line {
+        text: header.to_string(),
+        ..line.clone()
+    };
+    let mut words = interpreter.split_whitespace();
+    let program = words.next().unwrap_or("sh");
+    let mut child = std::process::Command::new(program)
+        .args(words)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::inherit())
+        .spawn()
+        .map_err(|err| {
+            crate::diagnostic::error_in(&at, "", format!("cannot run {}: {}", program, err));
+            err
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(code.as_bytes())?;
+        stdin.write_all(b"\n")?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let message = format!("script run by {} failed", interpreter);
+        crate::diagnostic::error_in(&at, "", message);
+        return Err(Box::new(crate::MakeError::ScriptError));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,107 @@
+//! Long-running `.SERVICE` targets, like a database for development.
+//!
+//! The recipe of a service is started in the background instead of being
+//! waited for. Its pid and output are kept in `.make-rs/services`.
+//! After the build, make-rs waits for the services it started and stops
+//! them when the build failed. `make-rs restart NAME` stops a running
+//! service and starts it again.
+
+use std::process::{Child, Command, Stdio};
+
+/// Directory that pidfiles and logs of services are kept in.
+const SERVICE_DIR: &str = ".make-rs/services";
+
+fn pidfile(name: &str) -> String {
+    format!("{}/{}.pid", SERVICE_DIR, name.replace('/', "_"))
+}
+
+fn logfile(name: &str) -> String {
+    format!("{}/{}.log", SERVICE_DIR, name.replace('/', "_"))
+}
+
+/// The pid of the service `name` if it is running.
+pub fn running(name: &str) -> Option<u32> {
+    let pid: u32 = std::fs::read_to_string(pidfile(name))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    // `kill -0` only checks whether the process exists.
+    let alive = Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    alive.then_some(pid)
+}
+
+/// Start the service `name` running `script` with `shell` in `directory`.
+pub fn start(
+    name: &str,
+    shell: &crate::shell::Shell,
+    script: &str,
+    directory: Option<&str>,
+) -> Result<Child, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(SERVICE_DIR)?;
+    let log = std::fs::File::create(logfile(name))?;
+    let mut command = shell.command(script);
+    command
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    if let Some(directory) = directory {
+        command.current_dir(directory);
+    }
+    let child = command.spawn()?;
+    std::fs::write(pidfile(name), child.id().to_string())?;
+    println!(
+        "make-rs: started service '{}' (pid {}), logging to {}",
+        name,
+        child.id(),
+        logfile(name)
+    );
+    Ok(child)
+}
+
+/// Stop the service `name` if it is running.
+pub fn stop(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(pid) = running(name) {
+        println!("make-rs: stopping service '{}' (pid {})", name, pid);
+        Command::new("kill").arg(pid.to_string()).status()?;
+    }
+    let _ = std::fs::remove_file(pidfile(name));
+    Ok(())
+}
+
+/// Wait until all `services` have exited.
+pub fn wait(services: Vec<(String, Child)>) -> Result<(), Box<dyn std::error::Error>> {
+    if services.is_empty() {
+        return Ok(());
+    }
+    println!("make-rs: waiting for services, interrupt to stop them");
+    for (name, mut child) in services {
+        let status = child.wait()?;
+        remove_pidfile(&name, child.id());
+        println!("make-rs: service '{}' exited ({})", name, status);
+    }
+    Ok(())
+}
+
+/// Stop all `services` started by this build.
+pub fn stop_all(services: Vec<(String, Child)>) {
+    for (name, mut child) in services {
+        println!("make-rs: stopping service '{}' (pid {})", name, child.id());
+        let _ = child.kill();
+        let _ = child.wait();
+        remove_pidfile(&name, child.id());
+    }
+}
+
+/// Remove the pidfile of `name` if it still belongs to `pid`
+/// (and not to a restarted instance of the service).
+fn remove_pidfile(name: &str, pid: u32) {
+    let path = pidfile(name);
+    if std::fs::read_to_string(&path).is_ok_and(|content| content.trim() == pid.to_string()) {
+        let _ = std::fs::remove_file(path);
+    }
+}
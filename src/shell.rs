@@ -0,0 +1,62 @@
+//! The shell recipes and `$(shell ...)` run their commands with.
+//!
+//! Like in GNU make, `SHELL` selects the program and `.SHELLFLAGS` its
+//! flags, but `SHELL` is never taken from the environment, where it is
+//! the user's login shell. Without `SHELL`, commands run with `sh -c`,
+//! or `cmd /C` on Windows.
+
+use crate::expand::{Expander, Origin};
+use std::process::Command;
+
+/// A shell and the flags that make it run a command given as the next argument.
+#[derive(Debug)]
+pub struct Shell {
+    program: String,
+    flags: Vec<String>,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::named(if cfg!(windows) { "cmd" } else { "sh" })
+    }
+}
+
+impl Shell {
+    /// The shell `program` with the flags it usually needs to run a command.
+    pub fn named(program: &str) -> Self {
+        let name = std::path::Path::new(program)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or(program)
+            .to_lowercase();
+        let flags = match name.as_str() {
+            "cmd" => vec!["/C"],
+            "powershell" | "pwsh" => vec!["-NoProfile", "-Command"],
+            _ => vec!["-c"],
+        };
+        Self {
+            program: program.to_string(),
+            flags: flags.into_iter().map(str::to_string).collect(),
+        }
+    }
+
+    /// The shell selected by `SHELL` and `.SHELLFLAGS` in `expander`.
+    pub fn from_variables(expander: &Expander) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut shell = match expander.origin("SHELL") {
+            None | Some(Origin::Environment) => Self::default(),
+            Some(_) => Self::named(expander.expand_rule("$(SHELL)")?.trim()),
+        };
+        if expander.origin(".SHELLFLAGS").is_some() {
+            let flags = expander.expand_rule("$(.SHELLFLAGS)")?;
+            shell.flags = flags.split_whitespace().map(str::to_string).collect();
+        }
+        Ok(shell)
+    }
+
+    /// A process running `script` with this shell.
+    pub fn command(&self, script: &str) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.flags).arg(script);
+        command
+    }
+}
@@ -0,0 +1,87 @@
+//! Signing of built artifacts with minisign or sigstore's cosign.
+
+use std::path::Path;
+
+/// Directory that signatures are written to.
+const SIGNATURE_DIR: &str = ".make-rs/signatures";
+
+/// The tools artifacts can be signed with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Minisign,
+    Cosign,
+}
+
+impl Tool {
+    /// Parse the `sign.tool` setting.
+    pub fn from_name(name: &str) -> Result<Self, crate::MakeError> {
+        match name {
+            "minisign" => Ok(Self::Minisign),
+            "cosign" | "sigstore" => Ok(Self::Cosign),
+            _ => {
+                crate::diagnostic::error(format!("unknown signing tool '{}'", name));
+                Err(crate::MakeError::InvalidConfig)
+            }
+        }
+    }
+
+    /// File extension of the signatures this tool produces.
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Minisign => "minisig",
+            Self::Cosign => "sig",
+        }
+    }
+}
+
+/// Sign every existing file in `artifacts` with `tool`, using the secret
+/// `key` if one is given (cosign signs keyless otherwise). The signatures
+/// are listed in `.make-rs/signatures/SIGNATURES` as `artifact signature` lines.
+pub fn sign(
+    tool: Tool,
+    key: Option<&str>,
+    artifacts: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(SIGNATURE_DIR)?;
+    let mut index = String::new();
+
+    for artifact in artifacts.iter().filter(|a| Path::new(a).is_file()) {
+        let signature = Path::new(SIGNATURE_DIR)
+            .join(format!(
+                "{}.{}",
+                artifact.replace('/', "_"),
+                tool.extension()
+            ))
+            .to_string_lossy()
+            .into_owned();
+        println!("sign {}", artifact);
+
+        let mut command = match tool {
+            Tool::Minisign => {
+                let mut command = std::process::Command::new("minisign");
+                command.args(["-S", "-m", artifact, "-x", &signature]);
+                if let Some(key) = key {
+                    command.args(["-s", key]);
+                }
+                command
+            }
+            Tool::Cosign => {
+                let mut command = std::process::Command::new("cosign");
+                command.args(["sign-blob", "--yes", "--output-signature", &signature]);
+                if let Some(key) = key {
+                    command.args(["--key", key]);
+                }
+                command.arg(artifact);
+                command
+            }
+        };
+        if !command.status()?.success() {
+            crate::diagnostic::error(format!("failed to sign {}", artifact));
+            return Err(Box::new(crate::MakeError::SigningFailed));
+        }
+        index.push_str(&format!("{} {}\n", artifact, signature));
+    }
+
+    std::fs::write(Path::new(SIGNATURE_DIR).join("SIGNATURES"), index)?;
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! The lines of a Makefile as the parser reads them, including the
+//! lines of the files it includes with `include`.
+
+use crate::conditional::Conditionals;
+use crate::expand::Expander;
+use crate::vfs::Fs;
+use std::collections::VecDeque;
+
+/// A line of a Makefile, with its location for diagnostics.
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub file: String,
+    pub number: usize,
+    pub text: String,
+    /// Whether `text` still holds the continued physical lines as they
+    /// are, separated by backslash-newline. See [join].
+    pub continued: bool,
+}
+
+/// The lines that are still to be parsed.
+#[derive(Debug, Default)]
+pub struct Lines {
+    pending: VecDeque<Line>,
+    /// Included files, each with the file that included it.
+    includers: Vec<(String, String)>,
+    /// The open conditional directives.
+    conditionals: Conditionals,
+}
+
+impl Lines {
+    /// The lines of `sources`, pairs of file names and contents, one after the other.
+    pub fn new(sources: &[(String, String)]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut lines = Self::default();
+        for (file, data) in sources {
+            lines.pending.extend(read(file, data)?);
+        }
+        Ok(lines)
+    }
+
+    /// Take the next line. Conditional directives are evaluated with
+    /// `expander` on the way, and the lines they exclude are skipped.
+    pub fn next(
+        &mut self,
+        expander: &Expander,
+    ) -> Result<Option<Line>, Box<dyn std::error::Error>> {
+        self.skip(expander)?;
+        Ok(self.pending.pop_front())
+    }
+
+    /// Look at the next line without taking it, like [Lines::next].
+    pub fn peek(
+        &mut self,
+        expander: &Expander,
+    ) -> Result<Option<&Line>, Box<dyn std::error::Error>> {
+        self.skip(expander)?;
+        Ok(self.pending.front())
+    }
+
+    /// Drop conditional directives and excluded lines until the next line
+    /// to be parsed, failing if a directive is not closed at the end.
+    /// Script blocks on the way are run, and their output comes next.
+    fn skip(&mut self, expander: &Expander) -> Result<(), Box<dyn std::error::Error>> {
+        while let Some(line) = self.pending.front_mut() {
+            join(line, expander.recipe_prefix());
+            if self.conditionals.handle(&*line, expander)? || !self.conditionals.active() {
+                self.pending.pop_front();
+                continue;
+            }
+            if !is_script(&line.text) {
+                return Ok(());
+            }
+            let Some(line) = self.pending.pop_front() else {
+                break;
+            };
+            // The output has the line number of the block.
+            let output = crate::script::run(&line)?;
+            for mut generated in read(&line.file, &output)?.into_iter().rev() {
+                generated.number = line.number;
+                self.pending.push_front(generated);
+            }
+        }
+        self.conditionals.finish()
+    }
+
+    /// Read the file `path` from `fs`, so its lines come next.
+    /// `from` is the `include` line, which must not be part of `path` or of
+    /// a file including it.
+    pub fn include(
+        &mut self,
+        fs: &dyn Fs,
+        path: &str,
+        from: &Line,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = from.file.as_str();
+        loop {
+            if file == path {
+                crate::diagnostic::error_in(
+                    from,
+                    path,
+                    format!("'{}' is already being included", path),
+                );
+                return Err(Box::new(crate::MakeError::CircularDependency));
+            }
+            match self.includers.iter().find(|(included, _)| included == file) {
+                Some((_, includer)) => file = includer,
+                None => break,
+            }
+        }
+
+        let data = fs.read_to_string(path).map_err(|err| {
+            crate::diagnostic::error_in(from, path, format!("cannot include {}: {}", path, err));
+            err
+        })?;
+        self.includers.push((path.to_string(), from.file.clone()));
+        for line in read(path, &data)?.into_iter().rev() {
+            self.pending.push_front(line);
+        }
+        Ok(())
+    }
+}
+
+/// Split the Makefile `file` into lines, leaving out empty lines and comments.
+/// Lines ending with a backslash are kept together with the next one until
+/// [join] knows whether they are recipe lines, and a `define` or `script`
+/// block becomes one line with the body after the first newline.
+fn read(file: &str, data: &str) -> Result<Vec<Line>, Box<dyn std::error::Error>> {
+    let mut lines = Vec::new();
+    let mut physical = data.lines().enumerate();
+    while let Some((index, line)) = physical.next() {
+        let mut text = line.to_string();
+        if is_directive(line, "define") {
+            text.truncate(text.trim_end().len());
+            text.push_str(&define_body(&mut physical, file, index + 1, line)?);
+            lines.push(Line {
+                file: file.to_string(),
+                number: index + 1,
+                text,
+                continued: false,
+            });
+            continue;
+        }
+        // A `script` line only opens a block if an `endscript` follows.
+        let closed = || physical.clone().any(|(_, next)| next.trim() == "endscript");
+        if crate::script::interpreter(line).is_some() && closed() {
+            text.truncate(text.trim_end().len());
+            for (_, next) in physical.by_ref() {
+                if next.trim() == "endscript" {
+                    break;
+                }
+                text.push('\n');
+                text.push_str(next);
+            }
+            // Even an empty block has its body after a newline.
+            if !text.contains('\n') {
+                text.push('\n');
+            }
+            lines.push(Line {
+                file: file.to_string(),
+                number: index + 1,
+                text,
+                continued: false,
+            });
+            continue;
+        }
+        let mut continued = false;
+        while text.ends_with('\\') {
+            let Some((_, next)) = physical.next() else {
+                break;
+            };
+            text.push('\n');
+            text.push_str(next);
+            continued = true;
+        }
+        if text.is_empty() || text.trim().starts_with('#') {
+            continue;
+        }
+        // Inline comments are cut off as well.
+        if let Some((before, _)) = text.split_once('#') {
+            text.truncate(before.len());
+        }
+        lines.push(Line {
+            file: file.to_string(),
+            number: index + 1,
+            text,
+            continued,
+        });
+    }
+    Ok(lines)
+}
+
+/// Join the continued physical lines of `line`, once the recipe `prefix`
+/// in effect for it is known. The shell gets recipe lines as they are,
+/// but without the prefix of the continuation lines. Elsewhere, the
+/// backslash and surrounding whitespace become one space.
+fn join(line: &mut Line, prefix: char) {
+    if !std::mem::take(&mut line.continued) {
+        return;
+    }
+    let recipe = line.text.starts_with(prefix);
+    let mut physical = line.text.split("\\\n");
+    let mut text = physical.next().unwrap_or_default().to_string();
+    for next in physical {
+        if recipe {
+            text.push_str("\\\n");
+            text.push_str(next.strip_prefix(prefix).unwrap_or(next));
+        } else {
+            text.truncate(text.trim_end().len());
+            text.push(' ');
+            text.push_str(next.trim_start());
+        }
+    }
+    line.text = text;
+}
+
+/// Whether `text` is a script block made by [read].
+fn is_script(text: &str) -> bool {
+    text.split_once('\n')
+        .is_some_and(|(header, _)| crate::script::interpreter(header).is_some())
+}
+
+/// Whether `line` is the directive `keyword`, which can't be a recipe line.
+fn is_directive(line: &str, keyword: &str) -> bool {
+    !line.starts_with('\t')
+        && line
+            .split_whitespace()
+            .next()
+            .is_some_and(|word| word == keyword)
+}
+
+/// Take the body of the `define` block started by `line`, up to the
+/// matching `endef`. The lines are kept as they are, with comments and
+/// nested `define`s. The body is returned with a newline in front.
+fn define_body<'a>(
+    physical: &mut impl Iterator<Item = (usize, &'a str)>,
+    file: &str,
+    number: usize,
+    line: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut body = String::new();
+    let mut depth = 0;
+    for (_, next) in physical {
+        if is_directive(next, "endef") {
+            if depth == 0 {
+                return Ok(body);
+            }
+            depth -= 1;
+        } else if is_directive(next, "define") {
+            depth += 1;
+        }
+        body.push('\n');
+        body.push_str(next);
+    }
+    let line = Line {
+        file: file.to_string(),
+        number,
+        text: line.to_string(),
+        continued: false,
+    };
+    crate::diagnostic::error_in(&line, "", "missing 'endef'");
+    Err(Box::new(crate::MakeError::LineIsNotATarget))
+}
@@ -0,0 +1,68 @@
+//! A guard that pauses the build while the disk is almost full,
+//! instead of letting recipes fail with confusing ENOSPC errors.
+
+use std::time::Duration;
+
+/// How often free space is checked again while paused.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Parse a size like `500M`, `2G` or `1048576` (bytes).
+pub fn parse_size(size: &str) -> Result<u64, crate::MakeError> {
+    let size = size.trim();
+    let (number, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => size.split_at(index),
+        None => (size, ""),
+    };
+    let factor: u64 = match unit.to_ascii_uppercase().trim_end_matches(['B', 'I']) {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => 0,
+    };
+    match number.parse::<u64>() {
+        Ok(number) if factor != 0 => Ok(number * factor),
+        _ => {
+            crate::diagnostic::error(format!("invalid size '{}'", size));
+            Err(crate::MakeError::InvalidConfig)
+        }
+    }
+}
+
+/// The number of bytes available on the filesystem of the current
+/// directory, as reported by `df`. `None` if `df` failed.
+fn available() -> Option<u64> {
+    let output = std::process::Command::new("df")
+        .args(["-Pk", "."])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let kilobytes: u64 = stdout
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(3)?
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Wait until at least `minimum` bytes are free, warning once while paused.
+pub fn wait_for(minimum: u64) {
+    let mut warned = false;
+    while let Some(available) = available().filter(|available| *available < minimum) {
+        if !warned {
+            crate::diagnostic::warning(format!(
+                "only {} MiB of {} MiB free space left, pausing until there is more",
+                available >> 20,
+                minimum >> 20
+            ));
+            warned = true;
+        }
+        std::thread::sleep(RECHECK_INTERVAL);
+    }
+    if warned {
+        println!("make-rs: enough free space again, continuing");
+    }
+}
@@ -0,0 +1,49 @@
+//! `make-rs state export|import FILE`: moving the build state between
+//! machines, e.g. to restore incremental builds on ephemeral CI runners.
+//!
+//! The state is everything make-rs keeps in `.make-rs` (stamps, the
+//! manifest, outputs, provenance, duration history, ...) except for
+//! downloads and git checkouts, which CI systems usually cache on their own,
+//! and running services. Archives are written and read with `tar`, which
+//! picks the compression from the file extension (e.g. `state.tar.zst`).
+
+use crate::MakeError;
+
+/// Directory that make-rs keeps its state in.
+const STATE_DIR: &str = ".make-rs";
+
+/// Parts of the state that are not exported.
+const NOT_EXPORTED: [&str; 3] = ["cache", "git", "services"];
+
+/// Run `make-rs state ACTION FILE`.
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let [action, file] = args else {
+        crate::diagnostic::error("usage: make-rs state export|import FILE");
+        return Err(Box::new(MakeError::UsageError));
+    };
+    let mut tar = std::process::Command::new("tar");
+    match action.as_str() {
+        "export" => {
+            std::fs::create_dir_all(STATE_DIR)?;
+            tar.arg("-caf").arg(file);
+            for excluded in NOT_EXPORTED {
+                tar.arg(format!("--exclude={}/{}", STATE_DIR, excluded));
+            }
+            tar.arg(STATE_DIR);
+        }
+        "import" => {
+            tar.arg("-xf").arg(file);
+        }
+        _ => {
+            crate::diagnostic::error(format!("unknown state action '{}'", action));
+            return Err(Box::new(MakeError::UsageError));
+        }
+    }
+
+    println!("make-rs: {}ing build state {}", action, file);
+    if !tar.status()?.success() {
+        crate::diagnostic::error(format!("tar failed to {} {}", action, file));
+        return Err(Box::new(MakeError::BuildError));
+    }
+    Ok(())
+}
@@ -0,0 +1,209 @@
+//! The filesystem operations of the parser and the executor.
+//!
+//! They go through the [Fs] trait, so that make-rs can be run against
+//! something other than the real filesystem, like an in-memory tree for
+//! testing or an overlay for hermetic tools, given to
+//! [Makefile::with_fs](crate::Makefile::with_fs). This includes the
+//! file functions like `$(wildcard)` and `$(mtime)` and the state in
+//! `.make-rs`. Recipes themselves, and the `curl` and `git` commands
+//! downloading prerequisites, always run on the real filesystem.
+
+use std::io;
+use std::time::SystemTime;
+
+/// A filesystem make-rs reads Makefiles and checks and writes targets in.
+pub trait Fs: std::fmt::Debug + Send + Sync {
+    /// Whether `path` exists.
+    fn exists(&self, path: &str) -> bool;
+    /// Whether `path` is a directory.
+    fn is_dir(&self, path: &str) -> bool;
+    /// The names of the entries of the directory at `path`.
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>>;
+    /// The modification time of `path`.
+    fn modified(&self, path: &str) -> io::Result<SystemTime>;
+    /// Read the contents of the file at `path`.
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+    /// Replace the contents of the file at `path`, creating missing directories.
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()>;
+    /// Remove the file at `path`.
+    fn remove(&self, path: &str) -> io::Result<()>;
+    /// Move the file at `from` to `to`, replacing it.
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+    /// Set the modification time of `path` to now, creating it if it doesn't exist.
+    fn touch(&self, path: &str) -> io::Result<()>;
+
+    /// Read the file at `path` as UTF-8.
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// The real filesystem.
+#[derive(Debug, Default)]
+pub struct Os;
+
+impl Fs for Os {
+    fn exists(&self, path: &str) -> bool {
+        std::path::Path::new(path).exists()
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        std::path::Path::new(path).is_dir()
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        std::fs::read_dir(path)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    fn modified(&self, path: &str) -> io::Result<SystemTime> {
+        std::fs::metadata(path)?.modified()
+    }
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, data)
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn touch(&self, path: &str) -> io::Result<()> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        file.set_modified(SystemTime::now())
+    }
+}
+
+/// A filesystem in which some files look like they were just modified,
+/// for `-W FILE`. Everything else is passed through to the inner one.
+#[derive(Debug)]
+pub struct WhatIf {
+    inner: Box<dyn Fs>,
+    files: Vec<String>,
+    now: SystemTime,
+}
+
+impl WhatIf {
+    /// Pretend that `files` in `inner` were modified now.
+    pub fn new(inner: Box<dyn Fs>, files: Vec<String>) -> Self {
+        Self {
+            inner,
+            files,
+            now: SystemTime::now(),
+        }
+    }
+}
+
+impl Fs for WhatIf {
+    fn exists(&self, path: &str) -> bool {
+        self.inner.exists(path)
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        self.inner.is_dir(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.inner.read_dir(path)
+    }
+
+    fn modified(&self, path: &str) -> io::Result<SystemTime> {
+        if self.files.iter().any(|file| file == path) {
+            return Ok(self.now);
+        }
+        self.inner.modified(path)
+    }
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        self.inner.write(path, data)
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        self.inner.remove(path)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.inner.rename(from, to)
+    }
+
+    fn touch(&self, path: &str) -> io::Result<()> {
+        self.inner.touch(path)
+    }
+}
+
+/// A filesystem in which some files look very old, for `-o FILE`.
+/// Everything else is passed through to the inner one.
+#[derive(Debug)]
+pub struct AssumeOld {
+    inner: Box<dyn Fs>,
+    files: Vec<String>,
+}
+
+impl AssumeOld {
+    /// Pretend that `files` in `inner` were modified long ago.
+    pub fn new(inner: Box<dyn Fs>, files: Vec<String>) -> Self {
+        Self { inner, files }
+    }
+}
+
+impl Fs for AssumeOld {
+    fn exists(&self, path: &str) -> bool {
+        self.inner.exists(path)
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        self.inner.is_dir(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.inner.read_dir(path)
+    }
+
+    fn modified(&self, path: &str) -> io::Result<SystemTime> {
+        let modified = self.inner.modified(path)?;
+        if self.files.iter().any(|file| file == path) {
+            return Ok(SystemTime::UNIX_EPOCH);
+        }
+        Ok(modified)
+    }
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        self.inner.write(path, data)
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        self.inner.remove(path)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.inner.rename(from, to)
+    }
+
+    fn touch(&self, path: &str) -> io::Result<()> {
+        self.inner.touch(path)
+    }
+}
@@ -0,0 +1,80 @@
+//! Search paths for prerequisites, from `VPATH` and `vpath` directives.
+//!
+//! A file prerequisite that doesn't exist is looked for in the
+//! directories of the `vpath` directives whose pattern matches its name,
+//! in the order of the Makefile, then in the directories of `VPATH`.
+//! Recipes see the path it was found at in `$<` and `$^`.
+
+use crate::vfs::Fs;
+
+/// The directories prerequisites are searched in.
+#[derive(Debug, Default, Clone)]
+pub struct SearchPath {
+    /// The patterns of `vpath pattern dirs` directives with their directories.
+    directives: Vec<(String, Vec<String>)>,
+    /// The directories of `VPATH`, which are searched for every file.
+    vpath: Vec<String>,
+}
+
+impl SearchPath {
+    /// Apply the (expanded) arguments of a `vpath` directive:
+    /// `vpath pattern dirs` adds directories for the files matching
+    /// `pattern`, `vpath pattern` removes them and `vpath` alone
+    /// removes all.
+    pub fn directive(&mut self, args: &str) {
+        let args = args.trim();
+        let (pattern, directories) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let directories = split(directories);
+        match pattern {
+            "" => self.directives.clear(),
+            _ if directories.is_empty() => self.directives.retain(|(p, _)| p != pattern),
+            _ => self.directives.push((pattern.to_string(), directories)),
+        }
+    }
+
+    /// Search the directories of the value of `VPATH`.
+    pub fn set_vpath(&mut self, value: &str) {
+        self.vpath = split(value);
+    }
+
+    /// Where the file `name` is in `fs`: `name` itself if it exists,
+    /// otherwise the first match in the search path.
+    pub fn find(&self, fs: &dyn Fs, name: &str) -> Option<String> {
+        if fs.exists(name) {
+            return Some(name.to_string());
+        }
+        if name.starts_with('/') {
+            return None;
+        }
+        let matching = self
+            .directives
+            .iter()
+            .filter(|(pattern, _)| matches(pattern, name))
+            .flat_map(|(_, directories)| directories);
+        matching
+            .chain(&self.vpath)
+            .map(|directory| format!("{}/{}", directory.trim_end_matches('/'), name))
+            .find(|path| fs.exists(path))
+    }
+}
+
+/// Split a list of directories separated by colons or whitespace.
+fn split(directories: &str) -> Vec<String> {
+    directories
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .filter(|directory| !directory.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether `name` matches the `vpath` pattern, in which `%` stands for anything.
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('%') {
+        Some((prefix, suffix)) => {
+            name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        }
+        None => pattern == name,
+    }
+}
@@ -0,0 +1,78 @@
+//! `--watch`: build again whenever a file the build reads changes.
+//!
+//! make-rs has no dependencies, so instead of filesystem notifications
+//! the modification times of the files are polled. A change is only acted
+//! on once the files stayed the same for a moment, so that saving several
+//! files at once (or an editor writing a file in steps) gives one build.
+
+use std::time::{Duration, SystemTime};
+
+/// How often the files are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the files must stay unchanged after a change before building.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Build with `options` again and again, waiting for a change in between,
+/// until make-rs is interrupted.
+pub fn run(options: crate::Options) -> Result<(), Box<dyn std::error::Error>> {
+    loop {
+        let mut files = Vec::new();
+        let result = crate::build(options.clone(), &mut files);
+        if files.is_empty() {
+            return result;
+        }
+        // The error of the last build is reported by cli.
+        if let Err(err) = &result {
+            if !crate::diagnostic::report(err.as_ref()) {
+                eprintln!("make-rs: {}", err);
+            }
+        }
+        let status = if result.is_ok() { "done" } else { "failed" };
+        let count = match files.len() {
+            1 => "1 file".to_string(),
+            n => format!("{} files", n),
+        };
+        println!("make-rs: build {}, watching {} for changes", status, count);
+        match wait(&files) {
+            Some(changed) => println!("make-rs: {} changed, building again", changed.join(", ")),
+            None => return result,
+        }
+    }
+}
+
+/// Wait until some of `files` change, returning them, or `None` if
+/// make-rs is interrupted meanwhile.
+fn wait(files: &[String]) -> Option<Vec<String>> {
+    let initial = snapshot(files);
+    let mut current = initial.clone();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if crate::engine::interrupted() {
+            return None;
+        }
+        let next = snapshot(files);
+        if next != current {
+            std::thread::sleep(DEBOUNCE);
+            current = snapshot(files);
+            continue;
+        }
+        if current != initial {
+            let changed = files
+                .iter()
+                .zip(initial.iter().zip(&current))
+                .filter(|(_, (before, after))| before != after)
+                .map(|(file, _)| file.clone())
+                .collect();
+            return Some(changed);
+        }
+    }
+}
+
+/// The modification time of each file, `None` if it doesn't exist.
+fn snapshot(files: &[String]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
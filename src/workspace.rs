@@ -0,0 +1,209 @@
+//! Workspaces: subprojects with their own Makefiles, loaded into one graph.
+//!
+//! The members are listed in `.make-rs.toml`:
+//!
+//! ```toml
+//! [workspace]
+//! members = ["lib", "app"]
+//! ```
+//!
+//! Targets of a member are named `member//target`, so they can be
+//! depended on from the root Makefile and from other members.
+//!
+//! With `--flatten`, recursive `$(MAKE) -C subdir` recipe lines are
+//! turned into dependencies on the targets of a member for `subdir`.
+
+use crate::{split_checksum, Makefile};
+use std::collections::HashSet;
+
+/// Load the Makefile of every member into `makefile`.
+pub fn load(makefile: &mut Makefile, members: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    for member in members {
+        load_member(makefile, member.trim_end_matches('/'))?;
+    }
+    link_files(makefile);
+    Ok(())
+}
+
+/// Load the Makefile of `member` and return the name of its default goal.
+fn load_member(
+    makefile: &mut Makefile,
+    member: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let Some(path) = crate::find_makefile(&*makefile.fs, member) else {
+        crate::diagnostic::error(format!("workspace member {} has no Makefile", member));
+        return Err(Box::new(crate::MakeError::NoTargets));
+    };
+    let source = crate::read_makefile(&*makefile.fs, &path)?;
+    let sub = Makefile::from_sources(
+        makefile.fs.clone(),
+        member,
+        makefile.options.expander()?,
+        &[source],
+    )?;
+    let default_goal = format!("{}//{}", member, sub.default_goal()?);
+
+    let local: HashSet<String> = sub.targets.iter().map(|t| t.name.clone()).collect();
+    for mut target in sub.targets {
+        target.name = format!("{}//{}", member, target.name);
+        target.dependencies = target
+            .dependencies
+            .iter()
+            .map(|dep| namespace(member, &local, dep))
+            .collect();
+        target.order_only = target
+            .order_only
+            .iter()
+            .map(|dep| namespace(member, &local, dep))
+            .collect();
+        target.waits = (target.waits.iter())
+            .map(|dep| namespace(member, &local, dep))
+            .collect();
+        target.group = (target.group.iter())
+            .map(|name| format!("{}//{}", member, name))
+            .collect();
+        target.directory = Some(member.to_string());
+        makefile.targets.push(target);
+    }
+    makefile.hooks.extend(sub.hooks);
+    makefile.members.insert(member.to_string(), sub.expander);
+    Ok(default_goal)
+}
+
+/// Replace recursive make invocations in recipes by dependencies on the
+/// targets of the sub-Makefiles, which are loaded as members if they aren't yet.
+/// Targets loaded this way are flattened as well.
+pub fn flatten(makefile: &mut Makefile) -> Result<(), Box<dyn std::error::Error>> {
+    let mut index = 0;
+    while index < makefile.targets.len() {
+        let base = makefile.targets[index].directory.clone();
+        let commands = std::mem::take(&mut makefile.targets[index].commands);
+        let mut kept = Vec::new();
+        let mut goals = Vec::new();
+        for command in commands {
+            let Some((directory, targets)) = recursive_make(&command) else {
+                kept.push(command);
+                continue;
+            };
+            let member = match &base {
+                Some(base) => format!("{}/{}", base, directory),
+                None => directory.to_string(),
+            };
+            let member = member.trim_end_matches('/').to_string();
+            let default_goal = if makefile.members.contains_key(&member) {
+                None
+            } else {
+                Some(load_member(makefile, &member)?)
+            };
+            if targets.is_empty() {
+                let goal = match default_goal {
+                    Some(goal) => goal,
+                    // The member was loaded before, so its first target is its default goal.
+                    None => makefile
+                        .targets
+                        .iter()
+                        .find(|t| t.directory.as_deref() == Some(member.as_str()))
+                        .map(|t| t.name.clone())
+                        .ok_or(crate::MakeError::NoTargets)?,
+                };
+                goals.push(goal);
+            }
+            goals.extend(targets.iter().map(|t| format!("{}//{}", member, t)));
+        }
+        let target = &mut makefile.targets[index];
+        target.commands = kept;
+        target.dependencies.extend(goals);
+        index += 1;
+    }
+    link_files(makefile);
+    Ok(())
+}
+
+/// Replace file prerequisites of member targets that name the file of
+/// a target in another member (like `../lib/libfoo.a`) by that target.
+fn link_files(makefile: &mut Makefile) {
+    let files: Vec<(String, String)> = makefile
+        .targets
+        .iter()
+        .filter(|t| t.directory.is_some())
+        .map(|t| (t.file(), t.name.clone()))
+        .collect();
+    for target in &mut makefile.targets {
+        if target.directory.is_none() {
+            continue;
+        }
+        for dep in target.dependencies.iter_mut().chain(&mut target.order_only) {
+            let (name, checksum) = split_checksum(dep);
+            if name.contains("//") || name.starts_with('/') {
+                continue;
+            }
+            let path = normalize(name);
+            if let Some((_, linked)) = files.iter().find(|(file, _)| *file == path) {
+                *dep = match checksum {
+                    Some(checksum) => format!("{}@sha256:{}", linked, checksum),
+                    None => linked.clone(),
+                };
+            }
+        }
+    }
+}
+
+/// Resolve `.` and `..` components of a relative path.
+fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if components.last().is_some_and(|c| *c != "..") => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// Recognize `$(MAKE) -C dir [targets]` (or `make`), returning the directory
+/// and targets. Invocations with other options are not recognized, since
+/// they may change what the sub-make does.
+fn recursive_make(command: &str) -> Option<(&str, Vec<&str>)> {
+    let mut words = command.split_whitespace();
+    if !["$(MAKE)", "${MAKE}", "make", "make-rs"].contains(&words.next()?) {
+        return None;
+    }
+    let mut directory = None;
+    let mut targets = Vec::new();
+    while let Some(word) = words.next() {
+        if word == "-C" {
+            directory = Some(words.next()?);
+        } else if let Some(dir) = word.strip_prefix("--directory=") {
+            directory = Some(dir);
+        } else if let Some(dir) = word.strip_prefix("-C") {
+            directory = Some(dir);
+        } else if word.starts_with('-') || word.contains(['=', ';', '&', '|', '>', '<']) {
+            return None;
+        } else {
+            targets.push(word);
+        }
+    }
+    Some((directory?, targets))
+}
+
+/// Rewrite a prerequisite of a target in `member` so it can be resolved
+/// from the workspace root: local targets get the `member//` prefix and
+/// relative files the `member/` directory.
+fn namespace(member: &str, local: &HashSet<String>, dep: &str) -> String {
+    let (name, checksum) = split_checksum(dep);
+    let name = if local.contains(name) {
+        format!("{}//{}", member, name)
+    } else if name.contains("//") || name.starts_with('/') {
+        // Other members, URLs and absolute paths stay as they are.
+        name.to_string()
+    } else {
+        format!("{}/{}", member, name)
+    };
+    match checksum {
+        Some(checksum) => format!("{}@sha256:{}", name, checksum),
+        None => name,
+    }
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn arguments_after_double_dash() {
+    let project = Project::new("run:\n\t@echo $(ARGS)\n");
+    let output = project.run(&["run", "--", "--port", "8080"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "--port 8080\n");
+}
+
+#[test]
+fn args_from_the_command_line() {
+    let project = Project::new("run:\n\t@echo $(ARGS)\n");
+    let output = project.run(&["run", "ARGS=--cli"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "--cli\n");
+}
+
+#[test]
+fn args_from_the_makefile() {
+    let project = Project::new("ARGS = --default\nrun:\n\t@echo $(ARGS)\n");
+    let output = project.run(&["run"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "--default\n");
+}
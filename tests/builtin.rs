@@ -0,0 +1,42 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn builtin_rules_compile_and_link() {
+    let project = Project::new("main: main.o\n");
+    project.write("main.c", "");
+    let output = project.run(&["-s", "CC=echo"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "-c -o main.o main.c\nmain.o -o main\n");
+}
+
+#[test]
+fn pattern_rules_of_the_makefile_win_over_builtin_ones() {
+    let project = Project::new("all: main.o\n%.o: %.c\n\t@echo user $< $@\n");
+    project.write("main.c", "");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "user main.c main.o\n");
+}
+
+#[test]
+fn no_builtin_rules() {
+    let project = Project::new("main: main.o\n");
+    project.write("main.c", "");
+    let output = project.run(&["-r"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn default_variables_can_be_overridden() {
+    let project = Project::new("CFLAGS = -O2\nall:\n\t@echo $(CC) $(RM) $(COMPILE.c)\n");
+    // The environment may have its own compiler, which wins over the default.
+    let output = project
+        .command(&["RM=del"])
+        .env_remove("CC")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "cc del cc -O2 -c\n");
+}
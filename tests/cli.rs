@@ -0,0 +1,70 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn unknown_goal_is_diagnosed() {
+    let project = Project::new("all:\n\t@true\n");
+    let output = project.run(&["nosuch"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        stderr(&output),
+        "make-rs: no rule to make target 'nosuch'\n"
+    );
+}
+
+#[test]
+fn failing_recipe_exits_with_two() {
+    let project = Project::new("all:\n\t@false\n");
+    let output = project.run(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        stderr(&output),
+        "make-rs: recipe for 'all' failed (exit code 1)\n"
+    );
+}
+
+#[test]
+fn keep_going_lists_the_failed_targets() {
+    let project = Project::new("all: a b\na:\n\t@false\nb:\n\t@false\n");
+    let output = project.run(&["-k"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).ends_with(
+            "make-rs: target 'all' not remade because of errors\n\
+             make-rs: 2 targets failed:\n\
+             make-rs:   'a': recipe failed (exit code 1)\n\
+             make-rs:   'b': recipe failed (exit code 1)\n"
+        ),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn question_exits_with_one_when_out_of_date() {
+    let project = Project::new("all:\n\t@true\n");
+    assert_eq!(project.run(&["-q"]).status.code(), Some(1));
+}
+
+#[test]
+fn assume_old_applies_to_file_functions() {
+    let project = Project::new("all:\n\t@echo $(mtime old) $(wildcard old)\n");
+    project.write("old", "");
+    let output = project.run(&["-o", "old"]);
+    assert_eq!(stdout(&output), "0 old\n");
+}
+
+#[test]
+fn question_removes_the_jobserver() {
+    let project = Project::new("all:\n\t@true\n");
+    std::fs::create_dir(project.path("tmp")).unwrap();
+    let output = project
+        .command(&["-q", "-j2"])
+        .env("TMPDIR", project.path("tmp"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let left: Vec<_> = std::fs::read_dir(project.path("tmp")).unwrap().collect();
+    assert!(left.is_empty(), "{:?}", left);
+}
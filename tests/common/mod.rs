@@ -0,0 +1,79 @@
+//! Running make-rs on Makefiles in temporary directories.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers the projects of a test binary.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory with a Makefile, which is removed when it is dropped.
+pub struct Project {
+    dir: PathBuf,
+}
+
+impl Project {
+    /// A project with `makefile` as its Makefile.
+    pub fn new(makefile: &str) -> Self {
+        let name = format!(
+            "make-rs-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let project = Self { dir };
+        project.write("Makefile", makefile);
+        project
+    }
+
+    /// The path of `name` in the project.
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Write the file `name` with `contents`.
+    pub fn write(&self, name: &str, contents: &str) {
+        std::fs::write(self.path(name), contents).unwrap();
+    }
+
+    /// Whether the file `name` exists.
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).exists()
+    }
+
+    /// make-rs with `args` in the project, without the `MAKEFLAGS`
+    /// of a make running the tests.
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_make-rs"));
+        command
+            .args(args)
+            .current_dir(&self.dir)
+            .env_remove("MAKEFLAGS")
+            .env_remove("MAKELEVEL");
+        command
+    }
+
+    /// Run make-rs with `args` in the project.
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The standard output of `output`.
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// The standard error of `output`.
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+/// The Makefile of the example in the conditional module, printing `FLAGS`.
+const FLAGS: &str = "\
+ifeq ($(CC),gcc)
+FLAGS = -fanalyzer
+else ifdef CLANG_FLAGS
+FLAGS = $(CLANG_FLAGS)
+else
+FLAGS = none
+endif
+all:
+\t@echo $(FLAGS)
+";
+
+#[test]
+fn the_first_true_branch_is_taken() {
+    let project = Project::new(FLAGS);
+    for (args, expected) in [
+        (&["CC=gcc"][..], "-fanalyzer\n"),
+        (
+            &["CC=clang", "CLANG_FLAGS=-Weverything"][..],
+            "-Weverything\n",
+        ),
+        (&["CC=clang"][..], "none\n"),
+    ] {
+        let output = project.run(args);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(stdout(&output), expected, "{:?}", args);
+    }
+}
+
+#[test]
+fn conditionals_nest_and_select_rules() {
+    let project = Project::new(
+        "X = 1\nifneq \"$(X)\" '2'\nifndef Y\nall:\n\t@echo inner\nendif\nelse\nall:\n\t@echo outer\nendif\n",
+    );
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "inner\n");
+    let output = project.run(&["X=2"]);
+    assert_eq!(stdout(&output), "outer\n");
+}
+
+#[test]
+fn missing_endif_is_an_error() {
+    let project = Project::new("ifdef X\nall:\n\t@true\n");
+    let output = project.run(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("missing 'endif'"),
+        "{}",
+        stderr(&output)
+    );
+}
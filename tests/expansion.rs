@@ -0,0 +1,44 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn undefined_variables_are_empty_in_recipes() {
+    let project = Project::new("all:\n\t@echo [$(UNDEFINED_VAR)]\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "[]\n");
+}
+
+#[test]
+fn single_character_variables() {
+    let project = Project::new("L = a b c\nall:\n\t@echo $(foreach f,$(L),$f.o)\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "a.o b.o c.o\n");
+}
+
+#[test]
+fn double_dollar_is_for_the_shell() {
+    let project = Project::new("all:\n\t@X=shell; echo $$X $$(echo sub)\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "shell sub\n");
+}
+
+#[test]
+fn wildcard_uses_basic_globs() {
+    let project = Project::new(
+        "all:\n\t@echo $(wildcard src/**/*.c src/{a,b}.c)\n\t@echo $(glob src/**/*.c)\n",
+    );
+    std::fs::create_dir_all(project.path("src/x/y")).unwrap();
+    for file in ["src/a.c", "src/x/b.c", "src/x/y/c.c", "src/{a,b}.c"] {
+        project.write(file, "");
+    }
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "src/x/b.c src/{a,b}.c\nsrc/a.c src/x/b.c src/x/y/c.c src/{a,b}.c\n"
+    );
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+const GENERATOR: &str = "all: a.h a.c\na.h a.c &: a.def\n\t@echo generate; touch a.h a.c\n";
+
+#[test]
+fn grouped_recipes_run_once() {
+    for jobs in ["-j1", "-j2"] {
+        let project = Project::new(GENERATOR);
+        project.write("a.def", "");
+        let output = project.run(&[jobs]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(stdout(&output), "generate\n", "{}", jobs);
+    }
+}
+
+#[test]
+fn grouped_targets_are_remade_together() {
+    let project = Project::new(GENERATOR);
+    project.write("a.def", "");
+    project.run(&[]);
+    let output = project.run(&["a.c"]);
+    assert_eq!(stdout(&output), "make-rs: 'a.c' is up to date.\n");
+    // A missing member makes the whole group out of date.
+    std::fs::remove_file(project.path("a.h")).unwrap();
+    let output = project.run(&["a.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "generate\n");
+    assert!(project.exists("a.h"));
+}
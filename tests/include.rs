@@ -0,0 +1,43 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn included_files_define_variables_and_targets() {
+    let project = Project::new("include vars.mk rules.mk\nall: lib\n\t@echo all $(NAME)\n");
+    project.write("vars.mk", "NAME = included\n");
+    project.write("rules.mk", "lib:\n\t@echo lib $(NAME)\n");
+    let output = project.run(&["all"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "lib included\nall included\n");
+}
+
+#[test]
+fn missing_includes_are_errors() {
+    let project = Project::new("include missing.mk\nall:\n\t@true\n");
+    let output = project.run(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("cannot include missing.mk"));
+}
+
+#[test]
+fn optional_includes_may_be_missing() {
+    let project = Project::new("-include missing.mk\nsinclude gone.mk\nall:\n\t@echo ok\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "ok\n");
+}
+
+#[test]
+fn include_cycles_are_errors() {
+    let project = Project::new("include a.mk\nall:\n\t@true\n");
+    project.write("a.mk", "include b.mk\n");
+    project.write("b.mk", "include a.mk\n");
+    let output = project.run(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("'a.mk' is already being included"),
+        "{}",
+        stderr(&output)
+    );
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::Project;
+use std::time::{Duration, Instant};
+
+/// Wait up to five seconds for `condition`.
+fn eventually(condition: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+/// Whether the process `pid` is still running (and not just a zombie).
+fn running(pid: &str) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .is_ok_and(|stat| !stat.split(") ").nth(1).unwrap_or("").starts_with('Z'))
+}
+
+#[test]
+fn terminating_stops_the_processes_recipes_started() {
+    // The recipe's shell starts another shell, which runs `sleep`.
+    let project = Project::new("all:\n\t@sh -c 'echo $$$$ > sleeper; exec sleep 30'; echo done\n");
+    let mut make = project.command(&[]).spawn().unwrap();
+    assert!(eventually(|| project.exists("sleeper")));
+    let sleeper = std::fs::read_to_string(project.path("sleeper")).unwrap();
+    let sleeper = sleeper.trim();
+    assert!(running(sleeper));
+
+    let status = std::process::Command::new("kill")
+        .args(["-TERM", &make.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(make.wait().unwrap().code(), Some(128 + 15));
+    assert!(eventually(|| !running(sleeper)));
+}
@@ -0,0 +1,42 @@
+use make_rs::{Event, MakeError, Makefile};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[test]
+fn observers_get_the_events_of_a_build() {
+    let mut makefile = Makefile::parse("all: dep\n\t@true\ndep:\n\t@true\n").unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let seen = events.clone();
+    makefile.observe(move |event| {
+        let event = match event {
+            Event::TargetStarted { target } => format!("started {}", target),
+            Event::TargetFinished { target, success } => format!("finished {} {}", target, success),
+            _ => return,
+        };
+        seen.lock().unwrap().push(event);
+    });
+    makefile.build("all").unwrap();
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            "started dep",
+            "finished dep true",
+            "started all",
+            "finished all true"
+        ]
+    );
+}
+
+#[test]
+fn cancelling_stops_running_commands() {
+    let mut makefile = Makefile::parse("all:\n\t@sleep 10\n").unwrap();
+    let token = makefile.cancellation_token();
+    let start = Instant::now();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        token.cancel();
+    });
+    let err = makefile.build("all").unwrap_err();
+    assert!(matches!(err.downcast_ref(), Some(MakeError::Cancelled)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{stderr, Project};
+
+/// The lines of the `--debug=jobs` log about starting and finishing targets.
+fn jobs(output: &std::process::Output) -> Vec<String> {
+    stderr(output)
+        .lines()
+        .filter(|line| line.contains("starting") || line.contains("finished"))
+        .map(|line| line.trim_start_matches("make-rs: [jobs] ").to_string())
+        .collect()
+}
+
+#[test]
+fn recipes_that_used_several_cores_take_as_many_slots() {
+    let project = Project::new("all: a b\na:\n\t@true\nb:\n\t@true\n");
+    std::fs::create_dir(project.path(".make-rs")).unwrap();
+    project.write(".make-rs/durations", "a\t1000\t2000\nb\t500\t500\n");
+    let output = project.run(&["-j2", "--debug=jobs"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        jobs(&output)[..4],
+        [
+            "starting 'a' with weight 2 (2 of 2 slots taken)",
+            "'a' finished, 1 ready",
+            "starting 'b' with weight 1 (1 of 2 slots taken)",
+            "'b' finished, 0 ready",
+        ]
+    );
+}
+
+#[test]
+fn independent_targets_run_at_the_same_time() {
+    // Each recipe waits for the other one to start, which only
+    // finishes if they run at the same time.
+    let wait = |mine: &str, other: &str| {
+        format!(
+            "\t@touch {}; for i in $$(seq 100); do [ -f {} ] && exit 0; sleep 0.05; done; exit 1\n",
+            mine, other
+        )
+    };
+    let makefile = format!("all: a b\na:\n{}b:\n{}", wait("a", "b"), wait("b", "a"));
+    let project = Project::new(&makefile);
+    let output = project.run(&["-j2"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn targets_wait_for_their_prerequisites() {
+    let project =
+        Project::new("c: a b\n\t@cat a b > c\na:\n\t@sleep 0.2; echo a > a\nb:\n\techo b > b\n");
+    let output = project.run(&["-s", "-j3"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let c = std::fs::read_to_string(project.path("c")).unwrap();
+    assert_eq!(c, "a\nb\n");
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn continued_recipe_lines_use_the_recipe_prefix() {
+    let project =
+        Project::new(".RECIPEPREFIX = >\nX = a \\\n    b\nall:\n>@echo one \\\n>two $(X)\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "one two a b\n");
+}
+
+#[test]
+fn continued_recipe_lines_go_to_the_shell() {
+    let project = Project::new("all:\n\t@printf '%s\\n' one \\\n\ttwo\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "one\ntwo\n");
+}
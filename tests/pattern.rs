@@ -0,0 +1,35 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn pattern_rules_make_matching_targets() {
+    let project = Project::new("all: main.o util.o\n%.o: %.c\n\t@echo $* $< $@; cp $< $@\n");
+    project.write("main.c", "main");
+    project.write("util.c", "util");
+    let output = project.run(&["-r"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "main main.c main.o\nutil util.c util.o\n");
+    let util = std::fs::read_to_string(project.path("util.o")).unwrap();
+    assert_eq!(util, "util");
+}
+
+#[test]
+fn pattern_rules_need_their_prerequisites() {
+    let project = Project::new("all: main.o\n%.o: %.c\n\t@cp $< $@\n");
+    let output = project.run(&["-r"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("'main.o', needed by 'all'"));
+}
+
+#[test]
+fn the_shortest_stem_wins() {
+    let project =
+        Project::new("all: lib/a.o\n%.o: %.c\n\t@echo any\nlib/%.o: %.c\n\t@echo lib $*\n");
+    project.write("a.c", "");
+    std::fs::create_dir(project.path("lib")).unwrap();
+    project.write("lib/a.c", "");
+    let output = project.run(&["-r"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "lib a\n");
+}
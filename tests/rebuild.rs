@@ -0,0 +1,41 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn up_to_date_targets_are_skipped() {
+    let project = Project::new("out: in\n\tcp in out\n");
+    project.write("in", "data");
+    let first = project.run(&["-s"]);
+    assert!(first.status.success(), "{}", stderr(&first));
+    let second = project.run(&[]);
+    assert!(second.status.success(), "{}", stderr(&second));
+    assert_eq!(stdout(&second), "make-rs: 'out' is up to date.\n");
+}
+
+#[test]
+fn force_prerequisite_always_remakes() {
+    let project = Project::new("out: FORCE\n\techo built >> out\nFORCE:\n");
+    for _ in 0..2 {
+        let output = project.run(&["-s"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    let out = std::fs::read_to_string(project.path("out")).unwrap();
+    assert_eq!(out, "built\nbuilt\n");
+}
+
+#[test]
+fn hash_remakes_when_the_expanded_recipe_changed() {
+    let project = Project::new("CFLAGS = -O1\nout: in\n\t@echo $(CFLAGS) > out\n");
+    project.write("in", "data");
+    let first = project.run(&["--hash"]);
+    assert!(first.status.success(), "{}", stderr(&first));
+    let unchanged = project.run(&["--hash"]);
+    assert_eq!(stdout(&unchanged), "make-rs: 'out' is up to date.\n");
+    let changed = project.run(&["--hash", "CFLAGS=-O2"]);
+    assert!(changed.status.success(), "{}", stderr(&changed));
+    let out = std::fs::read_to_string(project.path("out")).unwrap();
+    assert_eq!(out, "-O2\n");
+    let question = project.run(&["--hash", "-q", "CFLAGS=-O3"]);
+    assert_eq!(question.status.code(), Some(1));
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn script_output_is_parsed() {
+    let project = Project::new("script\necho 'all:'\necho '\techo generated'\nendscript\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("generated"));
+}
+
+#[test]
+fn assignment_to_script_is_not_a_block() {
+    let project = Project::new("script = deploy.sh\nall:\n\t@echo $(script)\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "deploy.sh\n");
+}
+
+#[test]
+fn script_in_false_conditional_does_not_run() {
+    let project = Project::new("ifeq (a,b)\nscript\ntouch ran\nendscript\nendif\nall:\n\t@true\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!project.exists("ran"));
+}
+
+#[test]
+fn lines_after_script_keep_their_numbers() {
+    let project = Project::new("script\necho 'X = 1'\nendscript\nall:\n\techo ok\noops\n");
+    let output = project.run(&[]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Makefile:6:"),
+        "{}",
+        stderr(&output)
+    );
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use common::{stderr, Project};
+
+#[test]
+fn plain_build_keeps_no_state() {
+    let project = Project::new("all: out\nout:\n\t@touch out\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(project.exists("out"));
+    assert!(!project.exists(".make-rs"));
+}
+
+#[test]
+fn keep_going_remembers_failed_targets() {
+    let project = Project::new("all: bad\nbad:\n\t@false\n");
+    project.run(&["-k"]);
+    let failed = std::fs::read_to_string(project.path(".make-rs/failed")).unwrap();
+    assert_eq!(failed, "bad\n");
+}
@@ -0,0 +1,19 @@
+mod common;
+
+use common::{stderr, stdout, Project};
+
+#[test]
+fn fetched_is_a_variable_without_url_prerequisites() {
+    let project = Project::new("FETCHED = mine\nall:\n\t@echo $(FETCHED)\n");
+    let output = project.run(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "mine\n");
+}
+
+#[test]
+fn checkout_is_a_variable_without_git_prerequisites() {
+    let project = Project::new("all:\n\t@echo $(CHECKOUT)\n");
+    let output = project.run(&["CHECKOUT=vendor"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "vendor\n");
+}
@@ -0,0 +1,149 @@
+use make_rs::vfs::Fs;
+use make_rs::{Event, Makefile};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// The contents and modification time of a file.
+type File = (Vec<u8>, SystemTime);
+
+/// Files in memory, shared between clones.
+#[derive(Debug, Default, Clone)]
+struct Memory(Arc<Mutex<HashMap<String, File>>>);
+
+impl Memory {
+    /// Add the file `path`, modified `seconds` after the epoch.
+    fn add(&self, path: &str, seconds: u64) {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+        self.0
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), (Vec::new(), modified));
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::from(io::ErrorKind::NotFound)
+}
+
+impl Fs for Memory {
+    fn exists(&self, path: &str) -> bool {
+        self.0.lock().unwrap().contains_key(path) || self.is_dir(path)
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        self.0
+            .lock()
+            .unwrap()
+            .keys()
+            .any(|file| file.starts_with(&prefix))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let prefix = match path.trim_end_matches('/') {
+            "." => String::new(),
+            dir => format!("{}/", dir),
+        };
+        let files = self.0.lock().unwrap();
+        let mut entries: Vec<String> = files
+            .keys()
+            .filter_map(|file| file.strip_prefix(&prefix))
+            .map(|rest| rest.split('/').next().unwrap().to_string())
+            .collect();
+        entries.dedup();
+        Ok(entries)
+    }
+
+    fn modified(&self, path: &str) -> io::Result<SystemTime> {
+        let files = self.0.lock().unwrap();
+        files
+            .get(path)
+            .map(|(_, modified)| *modified)
+            .ok_or_else(not_found)
+    }
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        let files = self.0.lock().unwrap();
+        files
+            .get(path)
+            .map(|(data, _)| data.clone())
+            .ok_or_else(not_found)
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        let mut files = self.0.lock().unwrap();
+        files.insert(path.to_string(), (data.to_vec(), SystemTime::now()));
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .remove(path)
+            .map(drop)
+            .ok_or_else(not_found)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut files = self.0.lock().unwrap();
+        let file = files.remove(from).ok_or_else(not_found)?;
+        files.insert(to.to_string(), file);
+        Ok(())
+    }
+
+    fn touch(&self, path: &str) -> io::Result<()> {
+        let mut files = self.0.lock().unwrap();
+        let file = files.entry(path.to_string());
+        file.or_insert_with(|| (Vec::new(), SystemTime::now())).1 = SystemTime::now();
+        Ok(())
+    }
+}
+
+/// Build `out` from `in` in `fs`, returning whether its recipe ran.
+fn remade(fs: &Memory) -> bool {
+    let mut makefile = Makefile::with_fs("out: in\n\t@true\n", Box::new(fs.clone())).unwrap();
+    let started = Arc::new(Mutex::new(false));
+    let seen = started.clone();
+    makefile.observe(move |event| {
+        if let Event::TargetStarted { target: "out" } = event {
+            *seen.lock().unwrap() = true;
+        }
+    });
+    makefile.build("out").unwrap();
+    let started = *started.lock().unwrap();
+    started
+}
+
+#[test]
+fn targets_are_checked_in_the_given_filesystem() {
+    let fs = Memory::default();
+    fs.add("in", 1);
+    fs.add("out", 2);
+    assert!(!remade(&fs));
+    fs.add("in", 3);
+    assert!(remade(&fs));
+}
+
+#[test]
+fn included_files_are_read_from_the_given_filesystem() {
+    let fs = Memory::default();
+    fs.write("rules.mk", b"X = memory\n").unwrap();
+    let makefile = Makefile::with_fs("include rules.mk\n$(X):\n", Box::new(fs)).unwrap();
+    assert!(makefile.target("memory").is_some());
+}
+
+#[test]
+fn file_functions_look_in_the_given_filesystem() {
+    let fs = Memory::default();
+    fs.add("a.c", 1);
+    fs.add("b.h", 2);
+    fs.add("src/c.c", 3);
+    let text = "all: $(wildcard *.c) $(glob **/*.c)\nstamp-$(mtime b.h):\n";
+    let makefile = Makefile::with_fs(text, Box::new(fs)).unwrap();
+    let all = makefile.target("all").unwrap();
+    assert_eq!(all.dependencies(), ["a.c", "a.c", "src/c.c"]);
+    assert!(makefile.target("stamp-2").is_some());
+}